@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::{ArgAction, Parser, Subcommand};
 
 #[derive(Debug, Parser)]
@@ -27,7 +29,11 @@ pub enum Command {
         root_url: String,
     },
     /// Display basic status information about an FTL site.
-    Status,
+    Status {
+        /// Output the report as JSON instead of human-readable text.
+        #[arg(long)]
+        json: bool,
+    },
     /// Invoke the FTL build pipeline.
     Build {
         /// Stay resident and trigger a new build whenever changes to the site source are detected.
@@ -45,6 +51,21 @@ pub enum Command {
         /// Run in development mode. Pages will auto-reload when a new revision is built.
         #[arg(short, long)]
         development: bool,
+        /// Serve the specified revision (by ID or user-provided name) as-is, without building
+        /// the site or watching for changes.
+        #[arg(short, long)]
+        revision: Option<String>,
+    },
+    /// Export a revision as a plain directory tree, suitable for any static file host.
+    /// 
+    /// Hooks cannot be exported; any that are skipped are listed in a manifest in the output directory.
+    /// Aliases and user-defined redirects are written as HTML redirect stubs, except for wildcard redirects.
+    Export {
+        /// Export the specified revision (by ID or user-provided name) instead of the live one.
+        #[arg(short, long)]
+        revision: Option<String>,
+        /// The directory to export into. Created if it does not exist.
+        out_dir: PathBuf,
     },
     /// Inspect and manipulate site revisions.
     #[command(subcommand)]
@@ -63,9 +84,19 @@ pub enum RevisionSubcommand {
         /// The ID or user-provided name of the revision to inspect.
         id: String
     },
+    /// Compare two revisions, listing the files, routes and rendered pages that changed between them.
+    Diff {
+        /// The ID or user-provided name of the older revision.
+        old: String,
+        /// The ID or user-provided name of the newer revision.
+        new: String,
+        /// Also print a unified diff of every rendered page that changed.
+        #[arg(short, long)]
+        patch: bool,
+    },
     /// Assigns a custom name to the specified revision.
     Name {
-        /// The ID hash of the revision to name.
+        /// The ID or current user-provided name of the revision to name.
         id: String,
         /// The name to assign to the revision.
         name: String
@@ -80,6 +111,13 @@ pub enum RevisionSubcommand {
         /// The ID or user-provided name of the revision to unpin.
         id: String
     },
+    /// Make the specified revision live, rolling the site back (or forward) to it.
+    /// 
+    /// `ftl serve` will serve the live revision without rebuilding until the next build.
+    Checkout {
+        /// The ID or user-provided name of the revision to check out.
+        id: String
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -3,6 +3,7 @@ use std::path::Path;
 
 use serde::{Deserialize, Serialize};
 
+use crate::db::RouteKind;
 use crate::prelude::*;
 
 /// Represents the contents of FTL's global configuration.
@@ -11,6 +12,14 @@ pub struct Config {
     pub root_url: String,
     pub build: Build,
     pub serve: Serve,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redirects: Vec<Redirect>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub taxonomies: Vec<Taxonomy>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub feeds: Vec<Feed>,
+    #[serde(default)]
+    pub sitemap: Sitemap,
     #[serde(default)]
     pub extra: HashMap<String, toml::Value>,
 }
@@ -27,6 +36,12 @@ pub struct Build {
     pub smart_punctuation: bool,
     pub highlight_code: bool,
     pub render_emoji: bool,
+    /// Whether or not to generate a static JSON search index for client-side search.
+    pub search_index: bool,
+    /// Whether or not to include a prebuilt inverted index (token to pages) in the search index.
+    pub search_index_inverted: bool,
+    pub images: Images,
+    pub minify: Minify,
 }
 
 impl Default for Build {
@@ -41,6 +56,90 @@ impl Default for Build {
             smart_punctuation: false,
             highlight_code: true,
             render_emoji: true,
+            search_index: false,
+            search_index_inverted: false,
+            images: Images::default(),
+            minify: Minify::default(),
+        }
+    }
+}
+
+/// Settings for output minification. Everything is disabled by default.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Minify {
+    /// Whether or not to minify rendered pages (along with their inline CSS and JavaScript.)
+    pub html: bool,
+    /// Whether or not to compile the stylesheet in compressed form.
+    pub css: bool,
+    /// Whether or not to minify `.js` assets.
+    pub js: bool,
+}
+
+/// Settings for derived (resized and converted) images.
+#[derive(Serialize, Deserialize, Debug, Hash)]
+#[serde(default)]
+pub struct Images {
+    /// The format that images are converted to, unless otherwise specified.
+    pub format: ImageFormat,
+    /// The encoding quality (from 1 to 100) of lossy formats. Doesn't apply to WebP, which is always lossless.
+    pub quality: u8,
+    /// Whether or not to add a `srcset` of resized copies to images in page content.
+    pub srcset: bool,
+    /// The widths of the copies listed in generated `srcset`s. Widths larger than the original image are skipped.
+    pub srcset_widths: Vec<u32>,
+    /// Whether or not to add `width` and `height` attributes to images in page content.
+    pub dimensions: bool,
+}
+
+impl Default for Images {
+    fn default() -> Self {
+        Images {
+            format: ImageFormat::Webp,
+            quality: 80,
+            srcset: false,
+            srcset_widths: vec![480, 960, 1440],
+            dimensions: false,
+        }
+    }
+}
+
+/// The formats that images can be converted to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    /// Lossless WebP. Lossy WebP isn't supported by the `image` crate, so photos are often
+    /// smaller as AVIF or JPEG.
+    Webp,
+    Avif,
+    Jpeg,
+}
+
+impl ImageFormat {
+    /// The file extension used for the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Webp => "webp",
+            Self::Avif => "avif",
+            Self::Jpeg => "jpg",
+        }
+    }
+}
+
+impl std::str::FromStr for ImageFormat {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "webp" => Ok(Self::Webp),
+            "avif" => Ok(Self::Avif),
+            "jpeg" | "jpg" => Ok(Self::Jpeg),
+            _ => {
+                let err = eyre!("Unsupported image format \"{s}\".")
+                    .suggestion("Supported formats are \"webp\", \"avif\" and \"jpeg\".");
+
+                bail!(err)
+            }
         }
     }
 }
@@ -66,7 +165,171 @@ pub struct Serve {
     pub address: String,
     pub port: u16,
     pub error_template: Option<String>,
+    pub cache_preheat: bool,
+    /// The order in which route classes are preheated. Classes that aren't listed are not preheated.
+    pub cache_preheat_priority: Option<Vec<PreheatClass>>,
     pub cache_max_size: u64,
     pub cache_ttl: u64,
     pub cache_tti: u64,
-}
\ No newline at end of file
+    /// Assets larger than this (in kilobytes) are streamed from disk rather than held in the in-memory cache.
+    /// Defaults to 1024 (1 MiB) if unset.
+    pub stream_threshold: Option<u64>,
+    /// Overrides for the MIME types of served files, keyed by file extension.
+    pub mime: HashMap<String, String>,
+}
+
+impl Serve {
+    /// The size (in bytes) above which assets are streamed rather than cached.
+    pub fn stream_threshold(&self) -> u64 {
+        self.stream_threshold.unwrap_or(1024) * 1024
+    }
+
+    /// The order in which route classes are preheated - pages, stylesheets, assets then aliases if unset.
+    pub fn cache_preheat_priority(&self) -> Vec<PreheatClass> {
+        match &self.cache_preheat_priority {
+            Some(priority) => priority.clone(),
+            None => vec![
+                PreheatClass::Pages,
+                PreheatClass::Stylesheets,
+                PreheatClass::Assets,
+                PreheatClass::Aliases,
+            ]
+        }
+    }
+}
+
+/// Classes of routes that can be preheated into the server's cache.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PreheatClass {
+    /// Pages, feeds, sitemaps and `robots.txt`.
+    Pages,
+    Stylesheets,
+    /// Plain and cachebusted asset routes, as well as the search index.
+    Assets,
+    /// Page aliases and the search index route (which are served as redirects.)
+    Aliases,
+}
+
+impl PreheatClass {
+    /// The route kinds that belong to this class.
+    pub fn kinds(self) -> &'static [RouteKind] {
+        match self {
+            Self::Pages => &[RouteKind::Page, RouteKind::Feed, RouteKind::Sitemap, RouteKind::Robots],
+            Self::Stylesheets => &[RouteKind::Stylesheet],
+            Self::Assets => &[RouteKind::Asset, RouteKind::RedirectAsset, RouteKind::SearchIndex],
+            Self::Aliases => &[RouteKind::RedirectPage, RouteKind::RedirectSearchIndex],
+        }
+    }
+}
+
+/// A user-defined redirect, declared in either `ftl.toml` or `redirects.toml`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Redirect {
+    /// The path to redirect from. A trailing `*` matches any suffix.
+    pub from: String,
+    /// The path or URL to redirect to. Any `*` is replaced with the suffix matched in `from`.
+    pub to: String,
+    /// The HTTP status code to redirect with.
+    #[serde(default = "Redirect::default_status")]
+    pub status: u16,
+}
+
+impl Redirect {
+    fn default_status() -> u16 {
+        301
+    }
+}
+
+/// A taxonomy (such as tags or categories), declared in `ftl.toml`.
+/// 
+/// Each distinct value of the attribute named by the taxonomy is a term, and every term gets a listing
+/// of its member pages at `/{name}/{term}`. The taxonomy itself gets an index at `/{name}`.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash)]
+pub struct Taxonomy {
+    /// The attribute the taxonomy is built from.
+    pub name: String,
+    /// The template used to render each term's listing.
+    pub template: String,
+    /// The template used to render the taxonomy's index. Defaults to `template`.
+    pub index_template: Option<String>,
+    /// The attribute to sort member pages by.
+    pub sort_by: Option<String>,
+    /// Whether or not to sort member pages in descending order.
+    #[serde(default)]
+    pub reverse: bool,
+}
+
+/// A feed of pages, declared in `ftl.toml`.
+/// 
+/// Every feed is rendered as both Atom (at `{path}/atom.xml`) and RSS 2.0 (at `{path}/rss.xml`.)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Feed {
+    /// The title of the feed.
+    pub title: String,
+    /// A short description of the feed.
+    #[serde(default)]
+    pub description: String,
+    /// The name of the feed's author. Defaults to the title of the feed.
+    pub author: Option<String>,
+    /// The route under which the feed's files are served. Defaults to the site root.
+    #[serde(default)]
+    pub path: String,
+    /// An SQL query selecting the IDs of the pages to include, in order.
+    pub query: Option<String>,
+    /// The attribute pages must have to be included, if not using a query.
+    /// Defaults to the date attribute.
+    pub attribute: Option<String>,
+    /// The value the attribute must have, if any.
+    pub value: Option<String>,
+    /// The maximum number of pages to include.
+    #[serde(default = "Feed::default_limit")]
+    pub limit: usize,
+    /// The attribute holding the publication date of each page.
+    #[serde(default = "Feed::default_date_attribute")]
+    pub date_attribute: String,
+    /// The attribute to use as the content of each item, instead of its rendered HTML.
+    pub summary_attribute: Option<String>,
+}
+
+impl Feed {
+    fn default_limit() -> usize {
+        20
+    }
+
+    fn default_date_attribute() -> String {
+        "date".to_owned()
+    }
+}
+
+/// Settings for the generated `sitemap.xml` and `robots.txt`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Sitemap {
+    /// Whether or not to generate a sitemap.
+    pub enabled: bool,
+    /// The attribute holding the last modification date of each page.
+    pub date_attribute: String,
+    /// Whether or not to generate a `robots.txt` pointing at the sitemap.
+    pub robots: bool,
+    /// The rules written to `robots.txt`, ahead of the sitemap's location.
+    pub robots_rules: String,
+}
+
+impl Default for Sitemap {
+    fn default() -> Self {
+        Sitemap {
+            enabled: false,
+            date_attribute: "date".to_owned(),
+            robots: true,
+            robots_rules: "User-agent: *\nAllow: /".to_owned(),
+        }
+    }
+}
+
+/// The contents of a `redirects.toml` file.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct RedirectsFile {
+    #[serde(default)]
+    pub redirects: Vec<Redirect>,
+}
@@ -25,13 +25,17 @@ pub struct InnerContext {
 
 impl InnerContext {
     pub fn init() -> Result<Context> {
-        let args = Arguments::parse();
+        let mut args = Arguments::parse();
 
         if let Command::Init { root_url } = &args.command {
             let mut cfg = Config {
                 root_url: root_url.to_owned(),
                 build: Build::default(),
                 serve: Serve::default(),
+                redirects: Vec::new(),
+                taxonomies: Vec::new(),
+                feeds: Vec::new(),
+                sitemap: Sitemap::default(),
                 extra: HashMap::new()
             };
 
@@ -80,13 +84,24 @@ impl InnerContext {
             std::process::exit(0);
         }
 
+        // Environment validation moves us to the site root, so relative output paths
+        // need to be resolved against the invocation directory first.
+        if let Command::Export { out_dir, .. } = &mut args.command {
+            *out_dir = env::current_dir()?.join(&*out_dir);
+        }
+
         let dir = validate_env()?;
 
         let config = dir.join(CONFIG_FILENAME);
         let config = Config::from_path(&config)?;
 
         let db = dir.join(SITE_DB_PATH);
-        let db = Database::open(db)?;
+        let db = match &args.command {
+            // Clearing the database also migrates it to the current schema,
+            // so we skip the version check.
+            Command::Db(DatabaseSubcommand::Clear) => Database::open_unchecked(db)?,
+            _ => Database::open(db)?,
+        };
 
         let ctx = InnerContext { config, args, db };
 
@@ -106,7 +121,17 @@ impl InnerContext {
 
     pub fn devel_mode(&self) -> bool {
         match self.args.command {
-            Command::Serve { development } => development,
+            Command::Serve { development, .. } => development,
+            _ => false
+        }
+    }
+
+    /// Whether or not the site is being rebuilt on every change for local development,
+    /// where build speed matters more than output size.
+    pub fn fast_rebuilds(&self) -> bool {
+        match self.args.command {
+            Command::Build { watch, .. } => watch,
+            Command::Serve { development, .. } => development,
             _ => false
         }
     }
@@ -5,6 +5,7 @@ mod args;
 mod output;
 mod config;
 mod context;
+mod pattern;
 
 use std::fmt::Display;
 use std::sync::Arc;
@@ -15,6 +16,7 @@ pub use args::*;
 pub use output::*;
 pub use config::*;
 pub use context::*;
+pub use pattern::*;
 
 use crate::prelude::*;
 
@@ -23,6 +25,7 @@ pub const AUTHORS: &str = env!("CARGO_PKG_AUTHORS");
 pub const REPOSITORY: &str = env!("CARGO_PKG_REPOSITORY");
 
 pub const CONFIG_FILENAME: &str = "ftl.toml";
+pub const REDIRECTS_FILENAME: &str = "redirects.toml";
 
 pub const SITE_INTERNAL_PATH: &str = ".ftl/";
 pub const SITE_DB_PATH: &str = ".ftl/ftl.db";
@@ -84,7 +84,11 @@ impl Display for Message {
                 "\n Build {}",
                 console::style("complete.").bold().bright().green()
             ),
-            BuildFail => todo!()
+            BuildFail => writeln!(
+                f,
+                "\n Build {}",
+                console::style("failed.").bold().bright().red()
+            )
         }
     }
 }
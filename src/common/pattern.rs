@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+
+use crate::prelude::*;
+
+/// A hook path containing named parameters (`/tags/:tag`) and/or a trailing wildcard (`/api/*rest`.)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutePattern {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// Matches only the exact text.
+    Literal(String),
+    /// Matches any single (non-empty) segment.
+    Param(String),
+    /// Matches the remainder of the path, which may be empty.
+    Wildcard(String),
+}
+
+impl RoutePattern {
+    /// Whether or not the provided path contains parameters or wildcards,
+    /// rather than being matched as-is.
+    pub fn is_pattern(path: &str) -> bool {
+        path.split('/').any(|segment| segment.starts_with([':', '*']))
+    }
+
+    pub fn parse(pattern: &str) -> Result<Self> {
+        let mut names = Vec::new();
+        let mut segments = Vec::new();
+        let mut split = split(pattern).peekable();
+
+        while let Some(segment) = split.next() {
+            let (segment, name) = match segment.split_at(segment.len().min(1)) {
+                (":", name) => (Segment::Param(name.to_owned()), name),
+                ("*", name) => {
+                    if split.peek().is_some() {
+                        bail!(
+                            eyre!("Wildcards must come at the end of a hook path (\"{pattern}\").")
+                                .suggestion("Move the wildcard segment to the end of the path.")
+                        )
+                    }
+
+                    (Segment::Wildcard(name.to_owned()), name)
+                }
+                _ => {
+                    segments.push(Segment::Literal(segment.to_owned()));
+                    continue;
+                }
+            };
+
+            if name.is_empty() {
+                bail!(
+                    eyre!("Hook path \"{pattern}\" has an unnamed parameter or wildcard.")
+                        .suggestion("Name the parameter, e.g. \"/tags/:tag\" or \"/api/*rest\".")
+                )
+            }
+
+            if names.contains(&name) {
+                bail!("Hook path \"{pattern}\" uses the parameter name \"{name}\" more than once.")
+            }
+
+            names.push(name);
+            segments.push(segment);
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Matches the pattern against the provided path, returning the captured (percent-decoded) parameters if successful.
+    pub fn matches(&self, path: &str) -> Option<BTreeMap<String, String>> {
+        let mut params = BTreeMap::new();
+        let mut split = split(path);
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => {
+                    if split.next()? != literal {
+                        return None
+                    }
+                }
+                Segment::Param(name) => {
+                    params.insert(name.to_owned(), decode(split.next()?));
+                }
+                Segment::Wildcard(name) => {
+                    let rest = split.by_ref().join("/");
+                    params.insert(name.to_owned(), decode(&rest));
+                }
+            }
+        }
+
+        match split.next() {
+            Some(_) => None,
+            None => Some(params)
+        }
+    }
+
+    /// A sort key ranking patterns from most to least specific.
+    /// 
+    /// Patterns are compared segment-by-segment, with literals beating parameters
+    /// and parameters beating wildcards - so `/tags/new` beats `/tags/:tag`, which beats `/tags/*rest`.
+    pub fn specificity(&self) -> Vec<u8> {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(_) => 0,
+                Segment::Param(_) => 1,
+                Segment::Wildcard(_) => 2,
+            })
+            .collect()
+    }
+}
+
+fn split(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+fn decode(segment: &str) -> String {
+    urlencoding::decode(segment)
+        .map(|segment| segment.into_owned())
+        .unwrap_or_else(|_| segment.to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matching() {
+        let tags = RoutePattern::parse("/tags/:tag").unwrap();
+        let api = RoutePattern::parse("/api/*rest").unwrap();
+
+        assert_eq!(tags.matches("/tags/rust").unwrap()["tag"], "rust");
+        assert_eq!(tags.matches("/tags/hello%20world/").unwrap()["tag"], "hello world");
+        assert!(tags.matches("/tags").is_none());
+        assert!(tags.matches("/tags/a/b").is_none());
+        assert!(tags.matches("/posts/rust").is_none());
+
+        assert_eq!(api.matches("/api/v1/users/42").unwrap()["rest"], "v1/users/42");
+        assert_eq!(api.matches("/api").unwrap()["rest"], "");
+        assert!(api.matches("/apis/v1").is_none());
+    }
+
+    #[test]
+    fn parsing() {
+        assert!(RoutePattern::is_pattern("/tags/:tag"));
+        assert!(RoutePattern::is_pattern("/api/*rest"));
+        assert!(!RoutePattern::is_pattern("/hook-example"));
+
+        assert!(RoutePattern::parse("/api/*rest/more").is_err());
+        assert!(RoutePattern::parse("/tags/:").is_err());
+        assert!(RoutePattern::parse("/:a/:a").is_err());
+    }
+
+    #[test]
+    fn precedence() {
+        let mut patterns = ["/tags/*rest", "/tags/:tag", "/tags/new"]
+            .map(|pattern| RoutePattern::parse(pattern).unwrap());
+
+        patterns.sort_by_key(RoutePattern::specificity);
+
+        assert_eq!(patterns[0], RoutePattern::parse("/tags/new").unwrap());
+        assert_eq!(patterns[2], RoutePattern::parse("/tags/*rest").unwrap());
+    }
+}
@@ -30,7 +30,7 @@ pub use rusqlite::{
 
 use crate::prelude::*;
 
-pub const SCHEMA_VERSION: i64 = 1;
+pub const SCHEMA_VERSION: i64 = 10;
 
 pub const AUX_UP: &str = include_str!("sql/aux_up.sql");
 pub const AUX_DOWN: &str = "DETACH DATABASE map;";
@@ -62,7 +62,18 @@ impl Database {
         }
     }
 
+    /// Opens the database at the provided path, verifying that its schema is compatible
+    /// with this version of FTL.
     pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let db = Self::open_unchecked(path)?;
+        db.check_version()?;
+        Ok(db)
+    }
+
+    /// Opens the database at the provided path without checking its schema version.
+    ///
+    /// This should only be used when the database is about to be wiped, such as by `ftl db clear`.
+    pub fn open_unchecked(path: impl Into<PathBuf>) -> Result<Self> {
         use std::ffi::c_int;
         use std::sync::Once;
         
@@ -95,7 +106,16 @@ impl Database {
             OpenFlags::SQLITE_OPEN_READ_ONLY,
         );
 
-        let version = ro_pool.get()?.pragma_query_value(
+        Ok(Self {
+            path,
+            rw_pool,
+            ro_pool,
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    fn check_version(&self) -> Result<()> {
+        let version = self.get_ro()?.pragma_query_value(
             None,
             "user_version",
             |row| row.get::<_, i64>(0)
@@ -103,18 +123,13 @@ impl Database {
 
         if version != SCHEMA_VERSION {
             let err = eyre!("Database schema is not compatible with this version of FTL.")
-                .note("Expected version {SCHEMA_VERSION}, got {version}.")
-                .suggestion("You may need to run `db clear` to update the schema.");
+                .note(format!("Expected version {SCHEMA_VERSION}, got {version}."))
+                .suggestion("You may need to run `ftl db clear` to update the schema.");
 
             bail!(err);
         }
 
-        Ok(Self {
-            path,
-            rw_pool,
-            ro_pool,
-            write_lock: Mutex::new(()),
-        })
+        Ok(())
     }
 
     pub fn compress(&self) -> Result<()> {
@@ -124,12 +139,30 @@ impl Database {
         conn.execute(
             "DELETE FROM revisions
             WHERE pinned = FALSE
+            AND live = FALSE
             AND time NOT IN (
                 SELECT MAX(time) FROM revisions
             )",
             []
         )?;
 
+        // The search index is a virtual table, so it can't cascade.
+        conn.execute(
+            "DELETE FROM search_index
+            WHERE revision NOT IN (
+                SELECT id FROM revisions
+            )",
+            []
+        )?;
+
+        conn.execute(
+            "DELETE FROM paginations
+            WHERE id NOT IN (
+                SELECT id FROM revision_files
+            )",
+            []
+        )?;
+
         conn.execute(
             "DELETE FROM input_files
             WHERE id NOT IN (
@@ -158,13 +191,20 @@ impl Database {
         for entry in std::fs::read_dir(SITE_CACHE_PATH)? {
             let path = entry?.path();
 
-            if !set.contains(&path) {
+            // Cached files are named after their ID, so we compare against
+            // the filename rather than the full path.
+            let Some(name) = path.file_name() else {
+                continue;
+            };
+
+            if !set.contains(Path::new(name)) {
                 std::fs::remove_file(&path)?;
             }
         }
 
         conn.execute("VACUUM;", [])?;
-        conn.execute("PRAGMA wal_checkpoint(FULL);", [])?;
+        // The checkpoint pragma returns a row, so it can't go through `execute`.
+        conn.execute_batch("PRAGMA wal_checkpoint(FULL);")?;
 
         Ok(())
     }
@@ -175,6 +215,7 @@ impl Database {
 
         conn.execute_batch(PRIME_DOWN)?;
         conn.execute_batch(PRIME_UP)?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
         conn.execute("VACUUM;", [])?;
 
         std::fs::remove_dir_all(SITE_CACHE_PATH)?;
@@ -185,6 +226,25 @@ impl Database {
 
     pub fn stat(&self) -> Result<()> {
         use indicatif::HumanBytes;
+
+        let (db_size, cache_size) = self.sizes()?;
+
+        let total = HumanBytes(cache_size + db_size);
+        let cache_size = HumanBytes(cache_size);
+        let db_size = HumanBytes(db_size);
+        
+        eprintln!(
+            "Database:    {}\nAsset cache: {}\nTotal:       {}",
+            db_size,
+            cache_size,
+            total,        
+        );
+
+        Ok(())
+    }
+
+    /// Computes the disk space (in bytes) consumed by the database and the asset cache, in that order.
+    pub fn sizes(&self) -> Result<(u64, u64)> {
         use walkdir::WalkDir;
 
         record! {
@@ -208,18 +268,7 @@ impl Database {
             Record::from_row
         )?.size;
 
-        let total = HumanBytes(cache_size + db_size);
-        let cache_size = HumanBytes(cache_size);
-        let db_size = HumanBytes(db_size);
-        
-        eprintln!(
-            "Database:    {}\nAsset cache: {}\nTotal:       {}",
-            db_size,
-            cache_size,
-            total,        
-        );
-
-        Ok(())
+        Ok((db_size, cache_size))
     }
 
     /// Acquire a read-write connection from the underlying pool, creating a new one
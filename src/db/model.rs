@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
 use exemplar::{
@@ -5,6 +6,7 @@ use exemplar::{
     BindResult, ExtrResult
 };
 
+use rusqlite::Statement;
 use rusqlite::types::ValueRef;
 
 use serde::{
@@ -76,6 +78,7 @@ pub struct Revision {
     pub time   : Option<String>,
     pub pinned : bool,
     pub stable : bool,
+    pub live   : bool,
 }
 
 #[derive(Debug, Clone, Model)]
@@ -98,6 +101,10 @@ pub struct Hook {
     pub template : String,
     pub headers  : String,
     pub cache    : bool,
+    pub cache_ttl            : Option<u32>,
+    pub cache_ignore_queries : String,
+    pub cache_vary           : String,
+    pub cache_max_entries    : Option<u32>,
 }
 
 /// Represents a URL route to a file.
@@ -125,6 +132,11 @@ sql_enum! {
     Stylesheet,
     RedirectPage,
     RedirectAsset,
+    SearchIndex,
+    Feed,
+    Sitemap,
+    Robots,
+    RedirectSearchIndex,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Model)]
@@ -136,12 +148,17 @@ pub struct Page {
     pub template   : Option<String>,
     pub offset     : i64,
     pub draft      : bool,
+    /// Whether or not the page is listed in the sitemap.
+    pub sitemap    : bool,
     #[bind(bind_cbor)]
     #[extr(extr_cbor)]
     pub attributes : TomlMap,
     #[bind(bind_cbor)]
     #[extr(extr_cbor)]
     pub extra      : TomlMap,
+    #[bind(bind_cbor)]
+    #[extr(extr_cbor)]
+    pub paginate   : Option<Paginate>,
 }
 
 impl Page {
@@ -175,6 +192,188 @@ impl Page {
     }
 }
 
+/// A page's pagination settings, declared in the `[paginate]` section of its frontmatter.
+///
+/// Items are selected either by an arbitrary SQL query, or by filtering the revision's pages on an attribute.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Paginate {
+    /// How many items to put on each page.
+    pub per_page  : usize,
+    /// An SQL query selecting the items to paginate.
+    pub query     : Option<String>,
+    /// The attribute a page must have to be paginated, if not using a query.
+    pub attribute : Option<String>,
+    /// The value the attribute must have, if any.
+    pub value     : Option<String>,
+    /// The attribute to sort pages by, if not using a query.
+    pub sort_by   : Option<String>,
+    /// Whether or not to sort pages in descending order.
+    #[serde(default)]
+    pub reverse   : bool,
+}
+
+impl Paginate {
+    /// Checks that exactly one item source has been specified, and that the page size is non-zero.
+    pub fn validate(&self) -> Result<()> {
+        if self.per_page == 0 {
+            bail!("Pagination page size (per_page) must be greater than zero.")
+        }
+
+        if self.query.is_some() == self.attribute.is_some() {
+            let err = eyre!("Pagination must specify exactly one of \"query\" or \"attribute\".")
+                .suggestion("Use \"query\" to paginate the results of an SQL query, or \"attribute\" to paginate pages with a given attribute.");
+
+            bail!(err)
+        }
+
+        Ok(())
+    }
+
+    /// Prepares a statement selecting the items of the pagination in order - either all of them,
+    /// or only those on the provided (1-based) page number.
+    ///
+    /// Queries can use the `:revision`, `:page` and `:drafts` named parameters, which are bound
+    /// to the current revision ID, the paginated page's ID and whether or not drafts are enabled.
+    pub fn statement<'c>(
+        &self,
+        conn: &'c rusqlite::Connection,
+        page_id: &str,
+        rev_id: &str,
+        drafts: bool,
+        number: Option<usize>,
+    ) -> Result<Statement<'c>> {
+        use rusqlite::ToSql;
+
+        let order = match self.reverse {
+            true => "DESC",
+            false => "ASC",
+        };
+
+        let sql = match &self.query {
+            Some(query) => query.trim().trim_end_matches(';').to_owned(),
+            None => format!("
+                SELECT pages.*, routes.route FROM pages
+                JOIN revision_files ON revision_files.id = pages.id
+                LEFT JOIN routes
+                    ON routes.id = pages.id
+                    AND routes.revision = :revision
+                    AND routes.kind = 2
+                WHERE revision_files.revision = :revision
+                AND pages.id != :page
+                AND (pages.draft = FALSE OR :drafts)
+                AND EXISTS (
+                    SELECT 1 FROM attributes
+                    WHERE attributes.id = pages.id
+                    AND attributes.kind = :attribute
+                    AND (:value IS NULL OR attributes.property = :value)
+                )
+                ORDER BY (
+                    SELECT MIN(property) FROM attributes
+                    WHERE attributes.id = pages.id
+                    AND attributes.kind = :sort_by
+                ) {order}, pages.path {order}
+            ")
+        };
+
+        let sql = match number {
+            Some(_) => format!("SELECT * FROM ({sql}) LIMIT :limit OFFSET :offset"),
+            None => sql,
+        };
+
+        let limit = self.per_page as i64;
+        let offset = number.map(|number| (number.saturating_sub(1) * self.per_page) as i64);
+
+        let mut stmt = conn.prepare(&sql)?;
+
+        // Pagination queries are run during routing, which has write access to the database.
+        if !stmt.readonly() {
+            bail!("Pagination queries must be read-only.")
+        }
+
+        let params: [(&str, &dyn ToSql); 8] = [
+            (":revision", &rev_id),
+            (":page", &page_id),
+            (":drafts", &drafts),
+            (":attribute", &self.attribute),
+            (":value", &self.value),
+            (":sort_by", &self.sort_by),
+            (":limit", &limit),
+            (":offset", &offset),
+        ];
+
+        // User queries may not use every parameter, so we only bind those that appear.
+        for (name, value) in params {
+            if let Some(i) = stmt.parameter_index(name)? {
+                stmt.raw_bind_parameter(i, value)?;
+            }
+        }
+
+        Ok(stmt)
+    }
+}
+
+/// Represents one page of a paginated page.
+#[derive(Debug, Clone, Model)]
+#[table("paginations")]
+#[check("sql/prime_up.sql")]
+pub struct Pagination {
+    /// The ID of the generated page.
+    /// Computed as the hash of the paginated page's ID, the page number, the total number of pages and the page's items.
+    pub id     : String,
+    /// The ID of the paginated page.
+    pub page   : String,
+    /// The (1-based) page number.
+    pub number : i64,
+    /// The total number of pages.
+    pub total  : i64,
+}
+
+impl Pagination {
+    /// Computes the route of the provided page number, given the route of the paginated page.
+    ///
+    /// The first page is served from the paginated page's own route, and the rest from `{route}/page/{number}`.
+    pub fn route(base: &str, number: i64) -> String {
+        match (base.trim_end_matches('/'), number) {
+            (_, 1) => base.to_owned(),
+            (base, number) => format!("{base}/page/{number}"),
+        }
+    }
+}
+
+/// Represents an image derived (resized and/or converted) from an image asset.
+///
+/// Derived images also have an [`InputFile`] entry, which is what they are routed and served through.
+#[derive(Debug, Clone, Model)]
+#[table("derived_images")]
+#[check("sql/prime_up.sql")]
+pub struct DerivedImage {
+    /// Computed as the hash of the source image's ID and the derivation parameters.
+    pub id     : String,
+    /// The ID of the source image.
+    pub source : String,
+    pub width  : u32,
+    pub height : u32,
+    pub format : String,
+}
+
+/// Represents the (cached) metadata of an image file.
+#[derive(Debug, Clone, Model)]
+#[table("image_metadata")]
+#[check("sql/prime_up.sql")]
+pub struct ImageMetadata {
+    /// The ID of the image file.
+    pub id             : String,
+    pub width          : u32,
+    pub height         : u32,
+    pub format         : String,
+    /// The image's EXIF fields, keyed by tag name.
+    #[bind(bind_cbor)]
+    #[extr(extr_cbor)]
+    pub exif           : BTreeMap<String, String>,
+    /// The image's dominant color, as a hex code (such as `#1f2e3d`.)
+    pub dominant_color : Option<String>,
+}
+
 #[derive(Debug, Clone, Model)]
 #[table("attributes")]
 #[check("sql/prime_up.sql")]
@@ -195,6 +394,10 @@ sql_enum! {
     Name => OutputKind,
     Page,
     Stylesheet,
+    SearchIndex,
+    Feed,
+    Sitemap,
+    Robots,
 }
 
 #[derive(Debug, Clone, Model)]
@@ -213,6 +416,8 @@ pub struct Output {
     pub id      : Option<String>,
     pub kind    : OutputKind,
     pub content : String,
+    pub gzip    : Vec<u8>,
+    pub brotli  : Vec<u8>,
 }
 
 fn bind_cbor<T: Serialize>(value: &T) -> BindResult {
@@ -257,4 +462,58 @@ fn extr_path(value: &ValueRef) -> ExtrResult<PathBuf> {
     Ok(
         value.as_str()?.into()
     )
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::{IN_MEMORY, PRIME_UP};
+
+    #[test]
+    fn pagination() {
+        assert_eq!(Pagination::route("/blog", 1), "/blog");
+        assert_eq!(Pagination::route("/blog/", 2), "/blog/page/2");
+        assert_eq!(Pagination::route("/", 3), "/page/3");
+
+        let conn = rusqlite::Connection::open(IN_MEMORY).unwrap();
+        conn.execute_batch(PRIME_UP).unwrap();
+        conn.execute_batch("
+            PRAGMA foreign_keys = OFF;
+            INSERT INTO revision_files VALUES ('blog', 'rev'), ('a', 'rev'), ('b', 'rev'), ('c', 'rev');
+            INSERT INTO pages (id, path, draft) VALUES
+                ('blog', 'content/blog.md', FALSE),
+                ('a', 'content/a.md', FALSE),
+                ('b', 'content/b.md', FALSE),
+                ('c', 'content/c.md', TRUE);
+            INSERT INTO attributes VALUES
+                ('blog', 'date', '2020'),
+                ('a', 'date', '2021'),
+                ('b', 'date', '2022'),
+                ('c', 'date', '2023');
+        ").unwrap();
+
+        let paginate = Paginate {
+            per_page: 1,
+            query: None,
+            attribute: Some("date".to_owned()),
+            value: None,
+            sort_by: Some("date".to_owned()),
+            reverse: true,
+        };
+
+        let ids = |drafts, number| -> Vec<String> {
+            paginate
+                .statement(&conn, "blog", "rev", drafts, number)
+                .unwrap()
+                .raw_query()
+                .mapped(|row| row.get("id"))
+                .map(Result::unwrap)
+                .collect()
+        };
+
+        assert_eq!(ids(false, None), ["b", "a"]);
+        assert_eq!(ids(true, None), ["c", "b", "a"]);
+        assert_eq!(ids(false, Some(2)), ["a"]);
+        assert!(ids(false, Some(3)).is_empty());
+    }
+}
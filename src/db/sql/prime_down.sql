@@ -1,8 +1,12 @@
 DROP TABLE IF EXISTS attributes;
+DROP TABLE IF EXISTS paginations;
+DROP TABLE IF EXISTS derived_images;
+DROP TABLE IF EXISTS image_metadata;
 DROP TABLE IF EXISTS routes;
 DROP TABLE IF EXISTS dependencies;
 DROP TABLE IF EXISTS output;
 DROP TABLE IF EXISTS output_hot;
+DROP TABLE IF EXISTS search_index;
 
 -- Tables with columns referenced by foreign key constraints
 -- need to be dropped *last*, or cryptic "table does not exist" 
@@ -39,7 +39,15 @@ CREATE TABLE revisions (
     -- 
     -- Revisions that do not successfully produce output are not stabilized,
     -- and will be discarded at next build time.
-    stable BOOLEAN
+    stable BOOLEAN,
+    -- Whether or not the revision is "live."
+    -- At most one revision is live at a time. Each successful build
+    -- makes its revision live, and older stable revisions can be made
+    -- live again using `ftl revision checkout`.
+    --
+    -- Like pinned revisions, the live revision is excluded when
+    -- performing database cleanup.
+    live BOOLEAN
 );
 
 -- Records one-to-many relationships between revisions and their files.
@@ -70,6 +78,8 @@ CREATE TABLE pages (
     offset INTEGER,
     -- Whether or not the page is a draft.
     draft BOOLEAN,
+    -- Whether or not the page is listed in the sitemap.
+    sitemap BOOLEAN DEFAULT TRUE,
     -- Bincode blob of the page's "attributes"  frontmatter section.
     --
     -- In addition to being stored here, the contents
@@ -82,6 +92,8 @@ CREATE TABLE pages (
     -- are not flattened into a separate table, as the "extra" 
     -- section is intended for page-specific properties.
     extra BLOB,
+    -- Bincode blob of the page's "paginate" frontmatter section, if any.
+    paginate BLOB,
 
     FOREIGN KEY (id)
     REFERENCES input_files (id)
@@ -91,6 +103,69 @@ CREATE TABLE pages (
 -- SQLite-recommended child key index.
 CREATE INDEX idx_pages_cfk ON pages(id);
 
+-- Records images derived (resized and/or converted) from image assets.
+-- Each derived image also has an input file entry, so that it can be routed and served like any other asset.
+CREATE TABLE derived_images (
+    -- The derived image's ID, generated by hashing the source ID and the derivation parameters together.
+    id TEXT PRIMARY KEY,
+    -- The file ID of the source image.
+    source TEXT,
+    -- The dimensions of the derived image, in pixels.
+    width INTEGER,
+    height INTEGER,
+    -- The format of the derived image (such as "webp".)
+    format TEXT,
+
+    FOREIGN KEY (id)
+    REFERENCES input_files (id)
+        ON DELETE CASCADE
+);
+
+-- Index over the source column, to quickly find the derived images of a file.
+CREATE INDEX idx_derived_images_source ON derived_images(source);
+
+-- Caches metadata about image files, which is expensive to compute.
+CREATE TABLE image_metadata (
+    -- The image's file ID.
+    id TEXT PRIMARY KEY,
+    -- The dimensions of the image, in pixels.
+    width INTEGER,
+    height INTEGER,
+    -- The format of the image (such as "jpeg".)
+    format TEXT,
+    -- CBOR blob of the image's EXIF fields, keyed by tag name.
+    exif BLOB,
+    -- The image's dominant color, as a hex code - or NULL if the image is fully transparent.
+    dominant_color TEXT,
+
+    FOREIGN KEY (id)
+    REFERENCES input_files (id)
+        ON DELETE CASCADE
+);
+
+-- Records the individual pages of paginated pages.
+-- Each page beyond the first gets its own route and output, keyed by its ID.
+CREATE TABLE paginations (
+    -- The ID of the generated page.
+    -- Computed from the paginated page's ID, the page number, 
+    -- the total number of pages and the page's items, so that 
+    -- any change to the page's contents results in a new ID.
+    id TEXT PRIMARY KEY,
+    -- The ID of the paginated page.
+    page TEXT,
+    -- The (1-based) page number.
+    number INTEGER,
+    -- The total number of pages.
+    total INTEGER,
+
+    FOREIGN KEY (page)
+    REFERENCES pages (id)
+        ON DELETE CASCADE
+);
+
+-- SQLite-recommended child key index.
+CREATE INDEX idx_paginations_cfk ON paginations(page);
+
 CREATE TABLE attributes (
     -- The ID of the page associated with the attribute.
     id TEXT,
@@ -125,6 +200,14 @@ CREATE TABLE hooks (
     headers TEXT,
     -- Whether or not the output of the hook should be cached in-memory.
     cache BOOLEAN,
+    -- How long (in seconds) cached output lives for, overriding the global TTL/TTI.
+    cache_ttl INTEGER,
+    -- Query parameters that don't affect the output of the hook, newline-separated.
+    cache_ignore_queries TEXT,
+    -- Request headers that do affect the output of the hook, newline-separated.
+    cache_vary TEXT,
+    -- The maximum number of cached outputs.
+    cache_max_entries INTEGER,
 
     FOREIGN KEY (id)
     REFERENCES input_files (id)
@@ -169,7 +252,11 @@ CREATE TABLE dependencies (
 CREATE TABLE output_hot (
     id TEXT PRIMARY KEY,
     kind INTEGER,
-    content TEXT
+    content TEXT,
+    -- Precompressed variants of the content, served to clients
+    -- that advertise support for them via Accept-Encoding.
+    gzip BLOB,
+    brotli BLOB
 );
 
 -- "Cold" output table used for long-term, self-contained storage of revisions.
@@ -179,6 +266,8 @@ CREATE TABLE output (
     revision TEXT,
     kind INTEGER,
     content TEXT,
+    gzip BLOB,
+    brotli BLOB,
 
     FOREIGN KEY (revision)
     REFERENCES revisions (id)
@@ -188,4 +277,22 @@ CREATE TABLE output (
 -- SQLite-recommended child key index.
 CREATE INDEX idx_output_cfk ON output(revision);
 -- Index over the ID and revision columns, to ensure maximum query speed when serving.
-CREATE INDEX idx_output_search ON output(id, revision);
\ No newline at end of file
+CREATE INDEX idx_output_search ON output(id, revision);
+
+-- Full-text search index over the rendered pages of each revision.
+-- Filled in when a revision is finalized.
+CREATE VIRTUAL TABLE search_index USING fts5(
+    -- The ID of the page.
+    id UNINDEXED,
+    -- The ID of the revision the page was rendered for.
+    revision UNINDEXED,
+    -- The route of the page.
+    route UNINDEXED,
+    -- The title attribute of the page, if any.
+    title,
+    -- The plaintext of the page's rendered output.
+    content,
+    -- The page's other attributes, space-separated.
+    attributes,
+    tokenize = 'porter unicode61'
+);
@@ -0,0 +1,405 @@
+//! Exporting revisions as plain static directory trees, as exposed by `ftl export`.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use console::style;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::db::*;
+use crate::prelude::*;
+use crate::render::feed;
+use crate::revision;
+use crate::serve::Redirects;
+
+/// The name of the manifest listing the hook routes that could not be exported.
+pub const HOOK_MANIFEST_FILENAME: &str = "ftl-skipped-hooks.json";
+
+#[derive(Debug, Serialize)]
+struct SkippedHook {
+    route: String,
+    id: String,
+    template: String,
+}
+
+#[derive(Debug, Default)]
+struct Summary {
+    pages: usize,
+    assets: usize,
+    redirects: usize,
+    hooks: usize,
+}
+
+record! {
+    Name     => HookRow,
+    route    => String,
+    id       => String,
+    template => String
+}
+
+/// Writes every route in a revision to the provided directory, such that it can be
+/// served by any static file server.
+///
+/// If no revision is specified, the live revision (or failing that, the most recent stable revision)
+/// is exported.
+pub fn export(ctx: &Context, target: Option<&str>, out_dir: &Path) -> Result<()> {
+    let revision = match target {
+        Some(target) => revision::resolve(ctx, target)?,
+        None => match revision::live(ctx)? {
+            Some(live) => live,
+            None => revision::latest_stable(ctx)?
+                .ok_or_else(|| eyre!("There are no stable revisions to export."))
+                .suggestion("Try running `ftl build` first.")?
+        }
+    };
+
+    if !revision.stable {
+        let err = eyre!("Cannot export revision {}, as it is not stable.", revision.id)
+            .note("Unstable revisions never finished building, so they have no output to export.");
+
+        bail!(err)
+    }
+
+    // Exporting into the site source would check the exported files into the next revision.
+    let site_root = std::env::current_dir()?;
+    let existed = out_dir.exists();
+
+    fs::create_dir_all(out_dir)?;
+    let out_dir = out_dir.canonicalize()?;
+
+    if out_dir.starts_with(&site_root) && !out_dir.starts_with(site_root.join(SITE_INTERNAL_PATH)) {
+        if !existed {
+            let _ = fs::remove_dir_all(&out_dir);
+        }
+
+        let err = eyre!("Cannot export into the site source directory ({out_dir:?}).")
+            .suggestion("Choose an output directory outside of the site.");
+
+        bail!(err)
+    }
+
+    info!("Exporting revision {} to {out_dir:?}...", revision.id);
+
+    let conn = ctx.db.get_ro()?;
+    let rev_id = revision.id.as_str();
+
+    let routes: Vec<Route> = conn
+        .prepare("
+            SELECT * FROM routes
+            WHERE revision = ?1
+            ORDER BY route
+        ")?
+        .query_and_then([rev_id], Route::from_row)?
+        .try_collect()?;
+
+    let outputs: HashMap<String, String> = conn
+        .prepare("
+            SELECT id, content FROM output
+            WHERE revision = ?1
+        ")?
+        .query_and_then([rev_id], |row| -> Result<_> {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .try_collect()?;
+
+    // Inline assets (such as JSON files) live in the database rather than the cache.
+    let inline: HashMap<String, Option<String>> = conn
+        .prepare("
+            SELECT input_files.id, input_files.contents FROM input_files
+            JOIN revision_files ON revision_files.id = input_files.id
+            WHERE revision_files.revision = ?1
+            AND input_files.inline = TRUE
+        ")?
+        .query_and_then([rev_id], |row| -> Result<_> {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .try_collect()?;
+
+    // Maps page IDs to their canonical (non-alias) route.
+    let canonical: HashMap<&str, &str> = routes
+        .iter()
+        .filter(|route| route.kind == RouteKind::Page)
+        .map(|route| (route.id.as_str(), route.route.as_str()))
+        .collect();
+
+    let hooks: Vec<SkippedHook> = conn
+        .prepare("
+            SELECT routes.route, hooks.id, hooks.template FROM routes
+            JOIN hooks ON hooks.id = routes.id
+            WHERE routes.revision = ?1
+            AND routes.kind = 1
+            ORDER BY routes.route
+        ")?
+        .query_and_then([rev_id], HookRow::from_row)?
+        .map_ok(|row| SkippedHook {
+            route: row.route,
+            id: row.id,
+            template: row.template,
+        })
+        .try_collect()?;
+
+    let redirects = Redirects::load(ctx, &rev_id.into())?;
+
+    // Every file is planned out before anything is written, so that references to
+    // renamed files can be rewritten.
+    let mut planned = Vec::with_capacity(routes.len());
+    let mut renames = Vec::new();
+    let mut written = HashSet::new();
+
+    // User-defined redirects take precedence over routes, just like when serving.
+    let mut stubs = Vec::new();
+
+    for (from, to) in redirects.exact() {
+        let relative = to_file_path(from)?.join("index.html");
+
+        if written.insert(relative.clone()) {
+            stubs.push((relative, to));
+        }
+    }
+
+    for route in &routes {
+        let mut relative = match route.kind {
+            RouteKind::Page | RouteKind::RedirectPage => to_file_path(&route.route)?.join("index.html"),
+            // The index itself is exported to the same path, since query strings are stripped.
+            RouteKind::Hook | RouteKind::RedirectSearchIndex => continue,
+            _ => to_file_path(&route.route)?,
+        };
+
+        if stubs.iter().any(|(stub, _)| *stub == relative) {
+            continue;
+        }
+
+        // Cachebusted routes only differ by their query string, which static file servers ignore -
+        // so colliding ones are written to a path that includes their ID instead.
+        if written.contains(&relative) {
+            if let Some(qualified) = qualify(&route.route) {
+                relative = to_file_path(&qualified)?;
+                renames.push((route.route.clone(), qualified));
+            }
+        }
+
+        if !written.insert(relative.clone()) {
+            let err = eyre!("Cannot export route {}, as another route maps to the same file ({relative:?}).", route.route)
+                .suggestion("Move or rename one of the conflicting pages or assets.");
+
+            bail!(err)
+        }
+
+        planned.push((route, relative));
+    }
+
+    let mut summary = Summary::default();
+
+    for (route, relative) in planned {
+        let destination = out_dir.join(&relative);
+
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        match route.kind {
+            RouteKind::Page
+            | RouteKind::Stylesheet
+            | RouteKind::SearchIndex
+            | RouteKind::Feed
+            | RouteKind::Sitemap
+            | RouteKind::Robots => {
+                let content = outputs
+                    .get(&route.id)
+                    .with_context(|| format!("Could not find output for route {}.", route.route))?;
+
+                fs::write(&destination, rewrite(content, &renames).as_bytes())?;
+
+                if route.kind == RouteKind::Page {
+                    summary.pages += 1;
+                }
+            }
+            RouteKind::Asset | RouteKind::RedirectAsset => {
+                if let Some(contents) = inline.get(&route.id) {
+                    fs::write(&destination, contents.as_deref().unwrap_or_default())?;
+                    summary.assets += 1;
+                    continue;
+                }
+
+                fs::copy(
+                    format!("{SITE_CACHE_PATH}{}", route.id),
+                    &destination
+                )
+                .with_context(|| format!("Failed to copy cached asset for route {}.", route.route))?;
+
+                summary.assets += 1;
+            }
+            RouteKind::RedirectPage => {
+                let target = canonical
+                    .get(route.id.as_str())
+                    .with_context(|| format!("Could not find the page aliased by route {}.", route.route))?;
+
+                fs::write(&destination, redirect_stub(target))?;
+                summary.redirects += 1;
+            }
+            RouteKind::Hook | RouteKind::RedirectSearchIndex => unreachable!(),
+        }
+    }
+
+    for (relative, target) in stubs {
+        let destination = out_dir.join(&relative);
+
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(&destination, redirect_stub(target))?;
+        summary.redirects += 1;
+    }
+
+    summary.hooks = hooks.len();
+
+    if !hooks.is_empty() {
+        fs::write(
+            out_dir.join(HOOK_MANIFEST_FILENAME),
+            serde_json::to_string_pretty(&hooks)?
+        )?;
+    }
+
+    eprintln!(
+        "Exported revision {} to {out_dir:?} ({} pages, {} assets, {} redirects).",
+        revision.id,
+        summary.pages,
+        summary.assets,
+        summary.redirects,
+    );
+
+    if summary.hooks != 0 {
+        eprintln!(
+            "{} skipped {} hook route(s), as hooks require `ftl serve` - see {HOOK_MANIFEST_FILENAME}.",
+            style("Note:").yellow().bold(),
+            summary.hooks
+        );
+    }
+
+    for from in redirects.wildcards() {
+        eprintln!(
+            "{} skipped the redirect from {from}*, as static file servers have no equivalent of wildcards.",
+            style("Note:").yellow().bold(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Converts a route into a relative filesystem path, stripping any query string.
+///
+/// Routes that would escape the output directory are rejected.
+fn to_file_path(route: &str) -> Result<PathBuf> {
+    let path = match route.split_once('?') {
+        Some((path, _)) => path,
+        None => route,
+    };
+
+    let path = Path::new(path.trim_start_matches('/'));
+
+    if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        bail!("Route {route} cannot be exported, as it is not a plain relative path.")
+    }
+
+    Ok(path.to_owned())
+}
+
+/// Converts a cachebusted route into one that includes its ID in the filename, such as
+/// `/static/cat.png?v=8da4b75f9dcca8f6` to `/static/cat.8da4b75f9dcca8f6.png`.
+///
+/// Returns `None` if the route isn't cachebusted.
+fn qualify(route: &str) -> Option<String> {
+    let (path, id) = route.split_once("?v=")?;
+    let (dir, name) = path.rsplit_once('/')?;
+
+    let name = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{stem}.{id}.{ext}"),
+        _ => format!("{name}.{id}"),
+    };
+
+    Some(format!("{dir}/{name}"))
+}
+
+/// Rewrites references to renamed routes in exported output, including the escaped form
+/// that templates produce.
+fn rewrite<'a>(content: &'a str, renames: &[(String, String)]) -> Cow<'a, str> {
+    let mut content = Cow::Borrowed(content);
+
+    for (from, to) in renames {
+        for (from, to) in [
+            (from.to_owned(), to.to_owned()),
+            (from.replace('/', "&#x2f;"), to.replace('/', "&#x2f;")),
+        ] {
+            if content.contains(&from) {
+                content = Cow::Owned(content.replace(&from, &to));
+            }
+        }
+    }
+
+    content
+}
+
+/// Generates an HTML stub that immediately redirects to the provided route.
+fn redirect_stub(target: &str) -> String {
+    let target = feed::escape(target);
+
+    indoc::formatdoc! {r#"
+        <!DOCTYPE html>
+        <html>
+        <head>
+            <meta charset="utf-8">
+            <title>Redirecting...</title>
+            <link rel="canonical" href="{target}">
+            <meta http-equiv="refresh" content="0; url={target}">
+        </head>
+        <body>
+            <p>Redirecting to <a href="{target}">{target}</a>...</p>
+        </body>
+        </html>
+    "#}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn file_paths() {
+        assert_eq!(to_file_path("/").unwrap(), PathBuf::new());
+        assert_eq!(to_file_path("/blog/post").unwrap(), PathBuf::from("blog/post"));
+        assert_eq!(
+            to_file_path("/static/java.png?v=8da4b75f9dcca8f6").unwrap(),
+            PathBuf::from("static/java.png")
+        );
+        assert!(to_file_path("/../etc/passwd").is_err());
+    }
+
+    #[test]
+    fn renames() {
+        assert_eq!(
+            qualify("/static/cat-480w.webp?v=8da4b75f9dcca8f6").as_deref(),
+            Some("/static/cat-480w.8da4b75f9dcca8f6.webp")
+        );
+        assert_eq!(qualify("/static/LICENSE?v=8da4b75f9dcca8f6").as_deref(), Some("/static/LICENSE.8da4b75f9dcca8f6"));
+        assert_eq!(qualify("/blog/post"), None);
+
+        let renames = [("/static/cat.png?v=1".to_owned(), "/static/cat.1.png".to_owned())];
+
+        assert_eq!(
+            rewrite(r#"<img src="/static/cat.png?v=1"><a href="&#x2f;static&#x2f;cat.png?v=1">"#, &renames),
+            r#"<img src="/static/cat.1.png"><a href="&#x2f;static&#x2f;cat.1.png">"#
+        );
+    }
+
+    #[test]
+    fn redirect_stubs() {
+        let stub = redirect_stub(r#"/search?q="><script>"#);
+
+        assert!(stub.contains(r#"url=/search?q=&quot;&gt;&lt;script&gt;""#));
+        assert!(!stub.contains("<script>"));
+    }
+}
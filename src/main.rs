@@ -1,9 +1,12 @@
 mod common;
 mod db;
+mod export;
 mod parse;
 mod prepare;
 mod render;
+mod revision;
 mod serve;
+mod status;
 mod watch;
 
 mod prelude {
@@ -65,46 +68,57 @@ fn main() -> Result<()> {
     info!("See {REPOSITORY} for more information.");
     
     match &ctx.args.command {
-        Status => {
-            todo!()
-        },
+        Status { json } => status::report(&ctx, *json)?,
         Build { watch, full, .. } => {
             if *full {
                 ctx.db.clear()?; 
             }
 
-            Renderer::new(&ctx, None)?;
+            let build = Renderer::new(&ctx, None);
             
             if *watch {
+                // Failed builds shouldn't kill the watcher - the last good revision
+                // stays live, and we try again on the next change.
+                if let Err(err) = build {
+                    report_watch_failure(&ctx, err);
+                }
+
                 let (_debouncer, mut rx) = watch::init_watcher(&ctx)?;
 
                 while let Ok(rev_id) = rx.blocking_recv() {
-                    Renderer::new(&ctx,Some(&rev_id))?;
+                    if let Err(err) = Renderer::new(&ctx, Some(&rev_id)) {
+                        report_watch_failure(&ctx, err);
+                    }
                 } 
+            } else {
+                build?;
             }
         },
-        Serve { .. } => {            
-            InnerServer::new(
-                &ctx,
-                Renderer::new(&ctx, None)?
-            ).serve()?;
+        Serve { revision, .. } => {
+            let server = match revision::serve_target(&ctx, revision.as_deref())? {
+                Some(revision) => InnerServer::new(
+                    &ctx,
+                    Renderer::from_revision(&ctx, &revision.id.into())?,
+                    false
+                )?,
+                None => InnerServer::new(
+                    &ctx,
+                    Renderer::new(&ctx, None)?,
+                    true
+                )?,
+            };
+
+            server.serve()?;
         }
+        Export { revision, out_dir } => export::export(&ctx, revision.as_deref(), out_dir)?,
         Revision(subcommand) => match subcommand {
-            List => {
-                todo!()
-            },
-            Inspect { id: _ } => {
-                todo!()
-            },
-            Name { id: _, name: _ } => {
-                todo!()
-            },
-            Pin { id: _ } => {
-                todo!()
-            },
-            Unpin { id: _ } => {
-                todo!()
-            }
+            List => revision::list(&ctx)?,
+            Inspect { id } => revision::inspect(&ctx, id)?,
+            Diff { old, new, patch } => revision::diff(&ctx, old, new, *patch)?,
+            Name { id, name } => revision::name(&ctx, id, name)?,
+            Pin { id } => revision::set_pinned(&ctx, id, true)?,
+            Unpin { id } => revision::set_pinned(&ctx, id, false)?,
+            Checkout { id } => revision::checkout(&ctx, id)?,
         },
         Db(subcommand) => match subcommand {
             Stat => ctx.db.stat()?,
@@ -119,6 +133,15 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+fn report_watch_failure(ctx: &Context, err: Report) {
+    if ctx.pretty_output() {
+        eprintln!("{err:?}");
+        eprintln!("Keeping the last good revision - waiting for changes...");
+    } else {
+        error!("Build failed - {err:?}");
+    }
+}
+
 fn install_logging() {
     use color_eyre::config::HookBuilder;
     use tracing_error::ErrorLayer;
@@ -20,13 +20,20 @@ struct Frontmatter {
     pub template: Option<String>,
     #[serde(default)]
     pub draft: bool,
+    #[serde(default = "Frontmatter::default_sitemap")]
+    pub sitemap: bool,
     #[serde(default)]
     pub attributes: TomlMap,
     #[serde(default)]
     pub extra: TomlMap,
+    pub paginate: Option<Paginate>,
 }
 
 impl Frontmatter {
+    fn default_sitemap() -> bool {
+        true
+    }
+
     pub fn map_attrs(&mut self) -> Result<()> {
         for value in self.attributes.values_mut() {
             match value {
@@ -72,8 +79,10 @@ impl Into<Page> for Frontmatter {
             offset: self.offset,
             template: self.template,
             draft: self.draft,
+            sitemap: self.sitemap,
             attributes: self.attributes,
             extra: self.extra,
+            paginate: self.paginate,
         }
     }
 }
@@ -148,6 +157,12 @@ fn extract_frontmatter(item: Row) -> Result<Page> {
     fm.id = item.id;
     fm.path = item.path;
     fm.offset = range.end as i64;
+    if let Some(paginate) = &fm.paginate {
+        paginate
+            .validate()
+            .with_context(|| format!("Invalid pagination settings for page at \"{}\".", fm.path))?;
+    }
+
     fm.map_attrs()?;
     fm.map_extra();
 
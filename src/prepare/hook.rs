@@ -1,3 +1,4 @@
+use axum::http::HeaderName;
 use itertools::Itertools;
 use serde::Deserialize;
 
@@ -10,7 +11,13 @@ struct TomlHook {
     template: String,
     #[serde(default)]
     headers: Vec<String>,
-    cache: bool
+    cache: bool,
+    cache_ttl: Option<u32>,
+    #[serde(default)]
+    cache_ignore_queries: Vec<String>,
+    #[serde(default)]
+    cache_vary: Vec<String>,
+    cache_max_entries: Option<u32>,
 }
 
 record! {
@@ -50,6 +57,10 @@ pub fn create_hooks(ctx: &Context, rev_id: &RevisionID) -> Result<()> {
             let mut paths = String::new();
 
             for path in hook.paths {
+                if RoutePattern::is_pattern(&path) {
+                    RoutePattern::parse(&path)?;
+                }
+
                 paths += &path;
                 paths += "\n";
             }
@@ -58,6 +69,14 @@ pub fn create_hooks(ctx: &Context, rev_id: &RevisionID) -> Result<()> {
                 paths.truncate(paths.len() - 1)
             }
 
+            // Varied headers are echoed back in the `Vary` header of cached responses.
+            if let Some(name) = hook.cache_vary.iter().find(|name| HeaderName::from_bytes(name.as_bytes()).is_err()) {
+                let err = eyre!("The hook using template \"{}\" varies its cache on an invalid header name (\"{name}\").", hook.template)
+                    .suggestion("Use a plain header name, such as \"Accept-Language\".");
+
+                bail!(err)
+            }
+
             let mut headers = String::new();
 
             for header in hook.headers {
@@ -75,6 +94,10 @@ pub fn create_hooks(ctx: &Context, rev_id: &RevisionID) -> Result<()> {
                 template: hook.template,
                 headers,
                 cache: hook.cache,
+                cache_ttl: hook.cache_ttl,
+                cache_ignore_queries: hook.cache_ignore_queries.join("\n"),
+                cache_vary: hook.cache_vary.join("\n").to_ascii_lowercase(),
+                cache_max_entries: hook.cache_max_entries,
             }.insert_or(&txn, OnConflict::Ignore)?;
 
             Ok(())
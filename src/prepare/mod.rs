@@ -3,7 +3,7 @@ mod hook;
 mod route;
 mod walking;
 
-pub use walking::walk_src;
+pub use walking::{peek_src, walk_src};
 
 use crate::prelude::*;
 
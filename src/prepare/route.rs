@@ -1,9 +1,12 @@
 use std::ffi::OsStr;
+use std::hash::{Hash, Hasher};
 use std::path::Path;
 
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 use regex::Regex;
+use rusqlite::types::ValueRef;
+use seahash::SeaHasher;
 
 use crate::db::*;
 use crate::prelude::*;
@@ -18,18 +21,38 @@ pub fn create_routes(ctx: &Context, rev_id: &RevisionID) -> Result<()> {
     let mut conn = ctx.db.get_rw()?;
     let txn = conn.transaction()?;
 
+    // Inline data files (JSON and TOML) in the asset and content directories
+    // are routed like any other asset.
+    //
+    // Derived images (which may already be linked to the revision, if it's being re-rendered)
+    // only have cachebusted routes.
     let mut query_static = txn.prepare("
         SELECT input_files.id, path FROM input_files
         JOIN revision_files ON revision_files.id = input_files.id
         WHERE revision_files.revision = ?1
-        AND input_files.inline = FALSE
+        AND input_files.id NOT IN (
+            SELECT id FROM derived_images
+        )
+        AND (
+            input_files.inline = FALSE
+            OR (
+                input_files.extension IN ('json', 'toml')
+                AND (input_files.path LIKE 'assets/%' OR input_files.path LIKE 'content/%')
+            )
+        )
     ")?;
 
     let mut query_cachebust = txn.prepare("
         SELECT input_files.id, path FROM input_files
         JOIN revision_files ON revision_files.id = input_files.id
         WHERE revision_files.revision = ?1
-        AND input_files.inline = FALSE
+        AND (
+            input_files.inline = FALSE
+            OR (
+                input_files.extension IN ('json', 'toml')
+                AND (input_files.path LIKE 'assets/%' OR input_files.path LIKE 'content/%')
+            )
+        )
     ")?;
 
     let mut query_hooks = txn.prepare("
@@ -119,20 +142,10 @@ pub fn create_routes(ctx: &Context, rev_id: &RevisionID) -> Result<()> {
     let page_routes = query_pages
         .query_and_then([rev_id.as_ref()], Row::from_row)?
         .map_ok(|row| {
-            let route = to_route(&row.path);
-
-            let filename = Path::new(&route)
-                .file_stem()
-                .map(OsStr::to_str)
-                .map(Option::unwrap)
-                .unwrap_or_default();
-
-            let filepath = route.trim_end_matches(filename);
-
             Ok(Route {
                 id: row.id,
                 revision: rev_id.to_string(),
-                route: format!("/{filepath}{}", slug::slugify(filename)),
+                route: page_route(&row.path),
                 kind: RouteKind::Page,
             })
         })
@@ -165,12 +178,122 @@ pub fn create_routes(ctx: &Context, rev_id: &RevisionID) -> Result<()> {
     query_hooks.finalize()?;
     query_pages.finalize()?;
     query_alias.finalize()?;
+
+    // Paginated pages are routed last, as attribute-filtered items
+    // carry their (already inserted) routes.
+    create_paginations(ctx, rev_id, &txn)?;
     
     txn.commit()?;
     info!("Done computing routes.");
     Ok(())
 }
 
+/// Splits each paginated page in the revision into its individual pages, recording them
+/// and routing every page beyond the first.
+fn create_paginations(ctx: &Context, rev_id: &RevisionID, conn: &rusqlite::Connection) -> Result<()> {
+    let mut query_paginated = conn.prepare("
+        SELECT pages.* FROM pages
+        JOIN revision_files ON revision_files.id = pages.id
+        WHERE revision_files.revision = ?1
+    ")?;
+
+    let paginated = query_paginated
+        .query_and_then([rev_id.as_ref()], Page::from_row)?
+        .filter_ok(|page| page.paginate.is_some());
+
+    for page in paginated {
+        let page = page?;
+        let paginate = page.paginate.as_ref().unwrap();
+
+        let mut stmt = paginate.statement(
+            conn,
+            &page.id,
+            rev_id.as_ref(),
+            ctx.drafts_enabled(),
+            None
+        ).wrap_err_with(|| format!("Failed to prepare pagination query for page at \"{}\".", page.path))?;
+
+        // One hasher per page, each fed the items on that page.
+        let mut hashers = Vec::new();
+        let mut rows = stmt.raw_query();
+        let mut count = 0;
+
+        while let Some(row) = rows.next()? {
+            if count % paginate.per_page == 0 {
+                hashers.push(SeaHasher::new());
+            }
+
+            let hasher = hashers.last_mut().unwrap();
+
+            for i in 0..row.as_ref().column_count() {
+                hash_value(row.get_ref(i)?, hasher);
+            }
+
+            count += 1;
+        }
+
+        // Even an empty pagination has a first page.
+        if hashers.is_empty() {
+            hashers.push(SeaHasher::new());
+        }
+
+        let total = hashers.len() as i64;
+        let base = page_route(&page.path);
+
+        for (number, mut hasher) in (1..).zip(hashers) {
+            (&page.id, number, total).hash(&mut hasher);
+
+            let pagination = Pagination {
+                id: format!("{:016x}", hasher.finish()),
+                page: page.id.clone(),
+                number,
+                total,
+            };
+
+            RevisionFile {
+                id: pagination.id.clone(),
+                revision: rev_id.to_string(),
+            }.insert_or(conn, OnConflict::Ignore)?;
+
+            if number > 1 {
+                Route {
+                    id: pagination.id.clone(),
+                    revision: rev_id.to_string(),
+                    route: Pagination::route(&base, number),
+                    kind: RouteKind::Page,
+                }.insert_or(conn, OnConflict::Ignore)?;
+            }
+
+            pagination.insert_or(conn, OnConflict::Ignore)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn hash_value(value: ValueRef, hasher: &mut SeaHasher) {
+    match value {
+        ValueRef::Null => 0_u8.hash(hasher),
+        ValueRef::Integer(int) => int.hash(hasher),
+        ValueRef::Real(float) => float.to_bits().hash(hasher),
+        ValueRef::Text(bytes) | ValueRef::Blob(bytes) => bytes.hash(hasher),
+    }
+}
+
+fn page_route(path: &str) -> String {
+    let route = to_route(path);
+
+    let filename = Path::new(&route)
+        .file_stem()
+        .map(OsStr::to_str)
+        .map(Option::unwrap)
+        .unwrap_or_default();
+
+    let filepath = route.trim_end_matches(filename);
+
+    format!("/{filepath}{}", slug::slugify(filename))
+}
+
 static EXT_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new("[.][^.]+$").unwrap());
 
 fn to_route(path: &str) -> String {
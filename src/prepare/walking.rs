@@ -8,27 +8,23 @@ use walkdir::{DirEntry, WalkDir};
 
 use crate::db::*;
 use crate::prelude::*;
+use crate::render::minify;
 
 /// Walks the site directory for all valid content files.
 pub fn walk_src(ctx: &Context) -> Result<RevisionID> {
     info!("Starting source directory walk...");
 
-    let (handle, tx) = ctx.db.get_rw()?.prepare_consumer(consumer_handler);
+    let salts = Salts::new(ctx);
 
-    WalkDir::new(".")
-        .into_iter()
-        .filter_ok(|entry| {
-            (entry.file_type().is_file() || entry.file_type().is_symlink())
-                && !entry
-                    .path()
-                    .to_str()
-                    .map(|s| s.starts_with("./.ftl") || s.starts_with("./ftl.toml"))
-                    .unwrap_or(false)
-        })
+    let (handle, tx) = ctx.db.get_rw()?.prepare_consumer(move |conn, rx| {
+        consumer_handler(conn, rx, salts.scripts)
+    });
+
+    source_entries()
         .par_bridge()
         .try_for_each(|entry| -> Result<_> {
-            let entry = entry.map_err(Report::from).map(process_entry)?;
-            let _ = tx.send(entry?);
+            let entry = process_entry(entry?, &salts)?;
+            let _ = tx.send(entry);
             Ok(())
         })?;
     
@@ -40,7 +36,63 @@ pub fn walk_src(ctx: &Context) -> Result<RevisionID> {
         .expect("Database consumer thread should not panic.")
 }
 
-fn process_entry(entry: DirEntry) -> Result<(InputFile, u64)> {
+/// Computes the ID of the revision that the site source would currently produce,
+/// without touching the database or the asset cache.
+pub fn peek_src(ctx: &Context) -> Result<RevisionID> {
+    let salts = Salts::new(ctx);
+
+    let hash = source_entries()
+        .par_bridge()
+        .map(|entry| -> Result<_> {
+            let (_, id) = process_entry(entry?, &salts)?;
+            Ok(id)
+        })
+        .try_reduce(|| 0_u64, |a, b| Ok(a ^ b))?;
+
+    Ok(RevisionID::from(format!("{hash:016x}")))
+}
+
+/// Build settings that change the output of a file without changing the file itself.
+///
+/// These are hashed into the IDs of the files they affect, so that changing them
+/// invalidates any existing output (or cached copies.)
+#[derive(Debug, Clone, Copy)]
+struct Salts {
+    /// A hash of the settings that affect rendered pages.
+    pages: u64,
+    /// Whether or not scripts are minified.
+    scripts: bool,
+}
+
+impl Salts {
+    fn new(ctx: &Context) -> Self {
+        let mut hasher = seahash::SeaHasher::new();
+        ctx.build.images.hash(&mut hasher);
+        // Pages link to the stylesheet, whose hash depends on whether or not it is minified.
+        (ctx.build.minify.html, ctx.build.minify.css).hash(&mut hasher);
+
+        Self {
+            pages: hasher.finish(),
+            scripts: ctx.build.minify.js,
+        }
+    }
+}
+
+/// Walks the site directory, yielding every file that should be checked into a revision.
+fn source_entries() -> impl Iterator<Item = walkdir::Result<DirEntry>> {
+    WalkDir::new(".")
+        .into_iter()
+        .filter_ok(|entry| {
+            (entry.file_type().is_file() || entry.file_type().is_symlink())
+                && !entry
+                    .path()
+                    .to_str()
+                    .map(|s| s.starts_with("./.ftl") || s.starts_with("./ftl.toml"))
+                    .unwrap_or(false)
+        })
+}
+
+fn process_entry(entry: DirEntry, salts: &Salts) -> Result<(InputFile, u64)> {
     let Some(path) = entry.path().to_str() else {
         let err = eyre!("Encountered a non-UTF-8 path ({:?}).", entry.path())
             .suggestion("FTL only supports UTF-8 paths; make sure your directories and filenames are valid UTF-8.");
@@ -64,6 +116,16 @@ fn process_entry(entry: DirEntry) -> Result<(InputFile, u64)> {
         hash.hash(&mut hasher);
         path.hash(&mut hasher);
 
+        if is_page(&extension) {
+            salts.pages.hash(&mut hasher);
+        }
+
+        // Minified scripts are cached under a different ID, so that toggling minification
+        // doesn't leave stale copies in the cache.
+        if salts.scripts && is_script(&extension) {
+            "minified".hash(&mut hasher);
+        }
+
         let int_hash = hasher.finish();
         let hex_hash = format!("{int_hash:016x}");
 
@@ -102,7 +164,7 @@ fn process_entry(entry: DirEntry) -> Result<(InputFile, u64)> {
     Ok((file, int_id))
 }
 
-fn consumer_handler(conn: &mut Connection, rx: Receiver<(InputFile, u64)>) -> Result<RevisionID> {
+fn consumer_handler(conn: &mut Connection, rx: Receiver<(InputFile, u64)>, minify_js: bool) -> Result<RevisionID> {
     let txn = conn.transaction()?;
 
     let mut ids = Vec::new();
@@ -117,7 +179,18 @@ fn consumer_handler(conn: &mut Connection, rx: Receiver<(InputFile, u64)>) -> Re
 
             if !destination.exists() {
                 debug!("Caching non-inline file {:#?}", &file.path);
-                std::fs::copy(&file.path, destination)?;
+
+                match minify_js && is_script(&file.extension) {
+                    true => {
+                        let source = std::fs::read(&file.path)?;
+                        let path = file.path.to_string_lossy();
+
+                        std::fs::write(destination, minify::js(source, &path))?;
+                    }
+                    false => {
+                        std::fs::copy(&file.path, destination)?;
+                    }
+                }
             }
         }
 
@@ -141,6 +214,7 @@ fn consumer_handler(conn: &mut Connection, rx: Receiver<(InputFile, u64)>) -> Re
         time: None,
         pinned: false,
         stable: false,
+        live: false,
     }.insert_or(&txn, OnConflict::Ignore)?;
 
     for id in ids {
@@ -169,3 +243,13 @@ fn is_inline(ext: &Option<String>) -> bool {
         _ => false,
     }
 }
+
+/// Whether or not a file is a page.
+fn is_page(ext: &Option<String>) -> bool {
+    matches!(ext.as_deref(), Some("md"))
+}
+
+/// Whether or not a file is a script that can be minified.
+fn is_script(ext: &Option<String>) -> bool {
+    matches!(ext.as_deref(), Some("js"))
+}
@@ -0,0 +1,86 @@
+use std::io::Write;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::db::*;
+use crate::prelude::*;
+
+/// Brotli quality level - one below the maximum, which is disproportionately slow.
+const BROTLI_QUALITY: u32 = 10;
+/// Brotli quality level used for development rebuilds.
+const BROTLI_QUALITY_FAST: u32 = 1;
+/// Brotli window size (log2), as recommended by the reference encoder.
+const BROTLI_WINDOW: u32 = 22;
+
+impl Output {
+    /// Creates a new output row, precompressing its content with gzip and Brotli
+    /// so that it doesn't need to be compressed on every request.
+    ///
+    /// Development rebuilds (`build --watch` and `serve --development`) use the cheapest
+    /// compression levels instead, so they don't hold up the next reload. The output keeps
+    /// them until it is next rendered; `ftl build --full` recompresses everything.
+    pub fn compressed(ctx: &Context, id: impl Into<String>, kind: OutputKind, content: String) -> Result<Self> {
+        let (gzip_level, brotli_quality) = match ctx.fast_rebuilds() {
+            true => (Compression::fast(), BROTLI_QUALITY_FAST),
+            false => (Compression::best(), BROTLI_QUALITY),
+        };
+
+        let gzip = gzip(content.as_bytes(), gzip_level)?;
+        let brotli = brotli(content.as_bytes(), brotli_quality)?;
+
+        Ok(Self {
+            id: Some(id.into()),
+            kind,
+            content,
+            gzip,
+            brotli,
+        })
+    }
+}
+
+fn gzip(data: &[u8], level: Compression) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), level);
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+fn brotli(data: &[u8], quality: u32) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+
+    {
+        let mut encoder = brotli::CompressorWriter::new(&mut out, 4096, quality, BROTLI_WINDOW);
+        encoder.write_all(data)?;
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let content = "<p>Hello, world!</p>".repeat(64);
+
+        for (level, quality) in [(Compression::best(), BROTLI_QUALITY), (Compression::fast(), BROTLI_QUALITY_FAST)] {
+            let mut decoded = String::new();
+            flate2::read::GzDecoder::new(&*gzip(content.as_bytes(), level).unwrap())
+                .read_to_string(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, content);
+
+            let compressed = brotli(content.as_bytes(), quality).unwrap();
+            let mut decoded = String::new();
+            brotli::Decompressor::new(&*compressed, 4096)
+                .read_to_string(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, content);
+
+            assert!(compressed.len() < content.len());
+        }
+    }
+}
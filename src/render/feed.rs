@@ -0,0 +1,422 @@
+//! Atom and RSS 2.0 feeds for the feeds declared in `ftl.toml`.
+
+use std::fmt::Write;
+
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use itertools::Itertools;
+use lol_html::{element, rewrite_str, RewriteStrSettings};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::db::*;
+use crate::prelude::*;
+
+static BODY_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<body[^>]*>(.*)</body>").unwrap());
+
+/// The `updated` date of a feed with no items, which Atom still requires.
+const EPOCH: &str = "1970-01-01T00:00:00+00:00";
+
+#[derive(Debug)]
+struct Item {
+    title: String,
+    link: String,
+    date: DateTime<FixedOffset>,
+    /// Either HTML or plain text, depending on `html`.
+    content: String,
+    html: bool,
+}
+
+record! {
+    Name    => Row,
+    route   => Option<String>,
+    html    => Option<String>
+}
+
+/// Renders every feed declared in `ftl.toml`, routing them for the provided revision.
+///
+/// Must be run after the revision's pages have been rendered, as feeds can include their output.
+pub fn generate(ctx: &Context, rev_id: &RevisionID) -> Result<()> {
+    if let Some(feed) = ctx.feeds.iter().duplicates_by(|feed| route_prefix(feed)).next() {
+        let err = eyre!("More than one feed has the path \"{}\".", feed.path)
+            .note("Every feed is routed to atom.xml and rss.xml under its path, so feed paths must be unique.");
+
+        bail!(err)
+    }
+
+    for feed in &ctx.feeds {
+        generate_feed(ctx, rev_id, feed)
+            .wrap_err_with(|| format!("Failed to generate feed \"{}\".", feed.title))?;
+    }
+
+    Ok(())
+}
+
+fn generate_feed(ctx: &Context, rev_id: &RevisionID, feed: &Feed) -> Result<()> {
+    info!("Generating feed \"{}\"...", feed.title);
+
+    let conn = ctx.db.get_rw()?;
+    let items = load_items(ctx, rev_id, &conn, feed)?;
+
+    let root = ctx.root_url.trim_end_matches('/');
+    let path = route_prefix(feed);
+
+    let feeds = [
+        (format!("{path}/atom.xml"), atom(feed, root, &path, &items)),
+        (format!("{path}/rss.xml"), rss(feed, root, &path, &items)),
+    ];
+
+    for (route, content) in feeds {
+        let id = format!("{:016x}", seahash::hash(content.as_bytes()));
+
+        // Feeds are regenerated on every build, so any previous version has to be unrouted.
+        conn.execute("
+            DELETE FROM routes
+            WHERE route = ?1
+            AND revision = ?2
+            AND kind = ?3
+        ", params![route, rev_id.as_ref(), RouteKind::Feed])?;
+
+        let mut conflicts = conn.prepare("
+            SELECT NULL FROM routes
+            WHERE route = ?1
+            AND revision = ?2
+        ")?;
+
+        if conflicts.exists([route.as_str(), rev_id.as_ref()])? {
+            let err = eyre!("Feed route \"{route}\" conflicts with an existing route.")
+                .suggestion("Change the feed's path, or move or rename the page (or asset) at this route.");
+
+            bail!(err)
+        }
+
+        Route {
+            id: id.clone(),
+            revision: rev_id.to_string(),
+            route,
+            kind: RouteKind::Feed,
+        }.insert_or(&conn, OnConflict::Replace)?;
+
+        RevisionFile {
+            id: id.clone(),
+            revision: rev_id.to_string(),
+        }.insert_or(&conn, OnConflict::Ignore)?;
+
+        let mut exists = conn.prepare("
+            SELECT NULL FROM output_hot
+            WHERE id = ?1
+        ")?;
+
+        if !exists.exists([&id])? {
+            Output::compressed(ctx, id, OutputKind::Feed, content)?
+                .insert_or(&conn, OnConflict::Replace)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The route that a feed's files are placed under - either empty (for the site root) or a path with a leading slash.
+fn route_prefix(feed: &Feed) -> String {
+    match feed.path.trim_matches('/') {
+        "" => String::new(),
+        path => format!("/{path}"),
+    }
+}
+
+fn load_items(ctx: &Context, rev_id: &RevisionID, conn: &Connection, feed: &Feed) -> Result<Vec<Item>> {
+    if feed.query.is_some() && feed.attribute.is_some() {
+        bail!("Feeds can specify at most one of \"query\" or \"attribute\".")
+    }
+
+    // Item selection works just like pagination, with the feed's items being the first page.
+    let filter = Paginate {
+        per_page: feed.limit,
+        query: feed.query.clone(),
+        attribute: match feed.query {
+            Some(_) => None,
+            None => Some(feed.attribute.clone().unwrap_or_else(|| feed.date_attribute.clone())),
+        },
+        value: feed.value.clone(),
+        sort_by: Some(feed.date_attribute.clone()),
+        reverse: true,
+    };
+
+    let mut stmt = filter.statement(conn, "", rev_id.as_ref(), ctx.drafts_enabled(), Some(1))?;
+
+    let ids: Vec<String> = stmt
+        .raw_query()
+        .mapped(|row| row.get("id"))
+        .try_collect()
+        .wrap_err("Feed queries must select an \"id\" column containing page IDs.")?;
+
+    let mut get_page = conn.prepare("
+        SELECT pages.*, routes.route, output_hot.content AS html FROM pages
+        LEFT JOIN routes
+            ON routes.id = pages.id
+            AND routes.revision = ?2
+            AND routes.kind = 2
+        LEFT JOIN output_hot ON output_hot.id = pages.id
+        WHERE pages.id = ?1
+    ")?;
+
+    let root = ctx.root_url.trim_end_matches('/');
+    let mut items = Vec::with_capacity(ids.len());
+
+    for id in ids {
+        let (page, row) = get_page.query_row(
+            [id.as_str(), rev_id.as_ref()],
+            |row| Ok((Page::from_row(row)?, Row::from_row(row)?))
+        )?;
+
+        let attribute = |name: &str| {
+            page.attributes
+                .get(name)
+                .and_then(|value| value.as_str())
+        };
+
+        let Some(route) = row.route else {
+            bail!("Page at \"{}\" is in a feed, but has no route.", page.path)
+        };
+
+        // Dates are usually TOML datetimes rather than strings.
+        let date = page.attributes
+            .get(&feed.date_attribute)
+            .and_then(|value| match value.as_str() {
+                Some(date) => parse_date(date),
+                None => parse_date(&value.to_string()),
+            })
+            .ok_or_else(|| {
+                eyre!("Page at \"{}\" is missing a valid \"{}\" attribute.", page.path, feed.date_attribute)
+                    .note("Feed items need a publication date.")
+                    .suggestion("Use a TOML datetime (such as 2023-01-01T12:00:00Z) or date (such as 2023-01-01.)")
+            })?;
+
+        let (content, html) = match &feed.summary_attribute {
+            Some(name) => (attribute(name).unwrap_or_default().to_owned(), false),
+            None => {
+                let html = row.html.unwrap_or_default();
+
+                let body = BODY_REGEX
+                    .captures(&html)
+                    .and_then(|captures| captures.get(1))
+                    .map_or(html.as_str(), |body| body.as_str());
+
+                (absolutize(body, root)?, true)
+            }
+        };
+
+        items.push(Item {
+            title: attribute("title").unwrap_or(&route).to_owned(),
+            link: format!("{root}{route}"),
+            date,
+            content,
+            html,
+        })
+    }
+
+    Ok(items)
+}
+
+fn atom(feed: &Feed, root: &str, path: &str, items: &[Item]) -> String {
+    let updated = items
+        .iter()
+        .map(|item| item.date)
+        .max()
+        .map_or_else(|| EPOCH.to_owned(), |date| date.to_rfc3339());
+
+    let author = feed.author.as_deref().unwrap_or(&feed.title);
+    let mut xml = String::new();
+
+    // Unwrap justification: writing to a String can't fail.
+    writeln!(xml, r#"<?xml version="1.0" encoding="utf-8"?>"#).unwrap();
+    writeln!(xml, r#"<feed xmlns="http://www.w3.org/2005/Atom">"#).unwrap();
+    writeln!(xml, "<title>{}</title>", escape(&feed.title)).unwrap();
+
+    if !feed.description.is_empty() {
+        writeln!(xml, "<subtitle>{}</subtitle>", escape(&feed.description)).unwrap();
+    }
+
+    writeln!(xml, r#"<link href="{}{path}/atom.xml" rel="self" type="application/atom+xml"/>"#, escape(root)).unwrap();
+    writeln!(xml, r#"<link href="{}/"/>"#, escape(root)).unwrap();
+    writeln!(xml, "<id>{}{path}/atom.xml</id>", escape(root)).unwrap();
+    writeln!(xml, "<updated>{updated}</updated>").unwrap();
+    writeln!(xml, "<author><name>{}</name></author>", escape(author)).unwrap();
+
+    for item in items {
+        let kind = match item.html {
+            true => "content type=\"html\"",
+            false => "summary type=\"text\"",
+        };
+
+        let close = kind.split(' ').next().unwrap();
+
+        writeln!(xml, "<entry>").unwrap();
+        writeln!(xml, "<title>{}</title>", escape(&item.title)).unwrap();
+        writeln!(xml, r#"<link href="{}" rel="alternate"/>"#, escape(&item.link)).unwrap();
+        writeln!(xml, "<id>{}</id>", escape(&item.link)).unwrap();
+        writeln!(xml, "<updated>{}</updated>", item.date.to_rfc3339()).unwrap();
+        writeln!(xml, "<{kind}>{}</{close}>", escape(&item.content)).unwrap();
+        writeln!(xml, "</entry>").unwrap();
+    }
+
+    writeln!(xml, "</feed>").unwrap();
+    xml
+}
+
+fn rss(feed: &Feed, root: &str, path: &str, items: &[Item]) -> String {
+    let mut xml = String::new();
+
+    writeln!(xml, r#"<?xml version="1.0" encoding="utf-8"?>"#).unwrap();
+    writeln!(xml, r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">"#).unwrap();
+    writeln!(xml, "<channel>").unwrap();
+    writeln!(xml, "<title>{}</title>", escape(&feed.title)).unwrap();
+    writeln!(xml, "<link>{}/</link>", escape(root)).unwrap();
+    writeln!(xml, "<description>{}</description>", escape(&feed.description)).unwrap();
+    writeln!(xml, r#"<atom:link href="{}{path}/rss.xml" rel="self" type="application/rss+xml"/>"#, escape(root)).unwrap();
+
+    if let Some(date) = items.iter().map(|item| item.date).max() {
+        writeln!(xml, "<lastBuildDate>{}</lastBuildDate>", date.to_rfc2822()).unwrap();
+    }
+
+    for item in items {
+        writeln!(xml, "<item>").unwrap();
+        writeln!(xml, "<title>{}</title>", escape(&item.title)).unwrap();
+        writeln!(xml, "<link>{}</link>", escape(&item.link)).unwrap();
+        writeln!(xml, r#"<guid isPermaLink="true">{}</guid>"#, escape(&item.link)).unwrap();
+        writeln!(xml, "<pubDate>{}</pubDate>", item.date.to_rfc2822()).unwrap();
+        writeln!(xml, "<description>{}</description>", escape(&item.content)).unwrap();
+        writeln!(xml, "</item>").unwrap();
+    }
+
+    writeln!(xml, "</channel>").unwrap();
+    writeln!(xml, "</rss>").unwrap();
+    xml
+}
+
+/// Parses a date attribute, which is either a full RFC 3339 datetime or a plain date (taken to be midnight UTC.)
+pub(super) fn parse_date(date: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(date).ok().or_else(|| {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+        let utc = FixedOffset::east_opt(0)?;
+
+        date.and_hms_opt(0, 0, 0)?
+            .and_local_timezone(utc)
+            .single()
+    })
+}
+
+/// Rewrites root-relative links and image sources (including `srcset` candidates) in the provided HTML
+/// to be absolute, as feed readers have no way of knowing where an item came from.
+fn absolutize(html: &str, root: &str) -> Result<String> {
+    let is_relative = |value: &str| value.starts_with('/') && !value.starts_with("//");
+
+    let rewrite = |value: Option<String>| {
+        value
+            .filter(|value| is_relative(value))
+            .map(|value| format!("{root}{value}"))
+    };
+
+    // Each candidate is a URL, optionally followed by a descriptor (such as "480w".)
+    let rewrite_srcset = |el: &mut lol_html::html_content::Element| {
+        let Some(srcset) = el.get_attribute("srcset") else {
+            return Ok(())
+        };
+
+        let srcset = srcset
+            .split(',')
+            .map(|candidate| {
+                let candidate = candidate.trim();
+
+                match is_relative(candidate) {
+                    true => format!("{root}{candidate}"),
+                    false => candidate.to_owned(),
+                }
+            })
+            .join(", ");
+
+        el.set_attribute("srcset", &srcset)
+    };
+
+    let rewritten = rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: vec![
+                element!("a[href]", |el| {
+                    if let Some(href) = rewrite(el.get_attribute("href")) {
+                        el.set_attribute("href", &href)?;
+                    }
+
+                    Ok(())
+                }),
+                element!("img[src]", |el| {
+                    if let Some(src) = rewrite(el.get_attribute("src")) {
+                        el.set_attribute("src", &src)?;
+                    }
+
+                    Ok(())
+                }),
+                element!("img[srcset], source[srcset]", |el| {
+                    rewrite_srcset(el)?;
+                    Ok(())
+                }),
+            ],
+            ..RewriteStrSettings::default()
+        }
+    )?;
+
+    Ok(rewritten)
+}
+
+/// Escapes the five XML special characters.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(char),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!(
+            parse_date("2022-09-14T15:00:00-05:00").unwrap().to_rfc2822(),
+            "Wed, 14 Sep 2022 15:00:00 -0500"
+        );
+        assert_eq!(
+            parse_date("2023-01-01").unwrap().to_rfc3339(),
+            "2023-01-01T00:00:00+00:00"
+        );
+        assert!(parse_date("yesterday").is_none());
+    }
+
+    #[test]
+    fn links() {
+        let html = r#"<p><a href="/about">About</a> <a href="//cdn.example.com/x">CDN</a> <img src="/static/a.png?v=1"></p>"#;
+
+        assert_eq!(
+            absolutize(html, "https://ftl.rs").unwrap(),
+            r#"<p><a href="https://ftl.rs/about">About</a> <a href="//cdn.example.com/x">CDN</a> <img src="https://ftl.rs/static/a.png?v=1"></p>"#
+        );
+
+        let html = r#"<picture><source srcset="/a-480w.webp 480w, https://cdn.example.com/b.webp 960w"><img srcset="/a.png 2x"></picture>"#;
+
+        assert_eq!(
+            absolutize(html, "https://ftl.rs").unwrap(),
+            r#"<picture><source srcset="https://ftl.rs/a-480w.webp 480w, https://cdn.example.com/b.webp 960w"><img srcset="https://ftl.rs/a.png 2x"></picture>"#
+        );
+        assert_eq!(escape("<a href=\"x\">Tom & 'Jerry'</a>"), "&lt;a href=&quot;x&quot;&gt;Tom &amp; &apos;Jerry&apos;&lt;/a&gt;");
+    }
+}
@@ -0,0 +1,385 @@
+//! Derived images - resized and/or converted copies of image assets.
+//!
+//! Derived images are encoded into the asset cache (named after their ID, like any other cached file)
+//! and get an input file entry, so they can be routed and served exactly like the asset they came from.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageReader};
+use itertools::Itertools;
+use seahash::SeaHasher;
+
+use crate::db::*;
+use crate::prelude::*;
+
+/// How quickly (from 1 to 10, higher is faster but larger) AVIF images are encoded.
+const AVIF_SPEED: u8 = 8;
+
+/// The extensions of the formats that images can be derived from.
+const SOURCE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp"];
+
+/// An image derived from an asset, along with the input file it is served through.
+#[derive(Debug, Clone)]
+pub struct Derived {
+    pub image: DerivedImage,
+    pub file: InputFile,
+}
+
+impl Derived {
+    /// The (cachebusted) route of the derived image.
+    pub fn route(&self) -> String {
+        self.file.cachebust()
+    }
+
+    /// Records the derived image in the database, and routes it for the provided revision.
+    pub fn register(&self, conn: &rusqlite::Connection, rev_id: &RevisionID) -> Result<()> {
+        self.file.insert_or(conn, OnConflict::Ignore)?;
+        self.image.insert_or(conn, OnConflict::Ignore)?;
+
+        link(conn, rev_id, &self.file)
+    }
+}
+
+/// Whether or not images can be derived from the provided file.
+pub fn is_image(file: &InputFile) -> bool {
+    !file.inline && file
+        .extension
+        .as_deref()
+        .map(str::to_lowercase)
+        .is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Loads the metadata of an image asset, computing it if it isn't already in the database.
+///
+/// Newly computed metadata is not stored - it must be inserted by the caller.
+pub fn metadata(ctx: &Context, file: &InputFile) -> Result<ImageMetadata> {
+    let conn = ctx.db.get_ro()?;
+
+    let mut query = conn.prepare_cached("
+        SELECT * FROM image_metadata
+        WHERE id = ?1
+    ")?;
+
+    if let Some(metadata) = query
+        .query_and_then([&file.id], ImageMetadata::from_row)?
+        .next()
+    {
+        return Ok(metadata?);
+    }
+
+    debug!("Computing metadata of image {:?}", file.path);
+
+    compute_metadata(file)
+        .wrap_err_with(|| format!("Failed to read the metadata of image {:?}.", file.path))
+}
+
+/// Derives a copy of the provided image, scaled down to the given width (if it is wider) and converted to the given format.
+///
+/// Derived images are identified by their source and parameters, so each one is only ever encoded once.
+/// The result must be [registered](Derived::register) before it can be served.
+pub fn derive(ctx: &Context, source: &InputFile, width: u32, format: ImageFormat) -> Result<Derived> {
+    if !is_image(source) {
+        let err = eyre!("Cannot derive an image from {:?}.", source.path)
+            .suggestion("Images can only be derived from JPEG, PNG, GIF and WebP files.");
+
+        bail!(err)
+    }
+
+    if width == 0 {
+        bail!("Derived image width must be greater than zero.")
+    }
+
+    // WebP is always encoded losslessly, so its quality has no effect.
+    let quality = match format {
+        ImageFormat::Webp => 100,
+        _ => ctx.build.images.quality.clamp(1, 100),
+    };
+
+    let mut hasher = SeaHasher::new();
+    (&source.id, width, format, quality).hash(&mut hasher);
+    let id = format!("{:016x}", hasher.finish());
+
+    let destination = PathBuf::from(format!("{SITE_CACHE_PATH}{id}"));
+
+    if destination.exists() {
+        if let Some(derived) = load(ctx, &id)? {
+            return Ok(derived);
+        }
+    }
+
+    debug!("Deriving {width}px {format:?} image from {:?}", source.path);
+
+    if format == ImageFormat::Webp {
+        static LOSSLESS: Once = Once::new();
+
+        LOSSLESS.call_once(|| {
+            warn!("WebP images are encoded losslessly (ignoring `quality`), and may be larger than their source - consider \"avif\" or \"jpeg\" for photos.");
+        });
+    }
+
+    let (bytes, (width, height)) = encode(source, width, format, quality)
+        .wrap_err_with(|| format!("Failed to derive an image from {:?}.", source.path))?;
+
+    std::fs::write(&destination, &bytes)?;
+
+    let file = InputFile {
+        id: id.clone(),
+        hash: format!("{:016x}", seahash::hash(&bytes)),
+        path: derived_path(&source.path, width, format),
+        extension: Some(format.extension().to_owned()),
+        contents: None,
+        inline: false,
+    };
+
+    let image = DerivedImage {
+        id,
+        source: source.id.clone(),
+        width,
+        height,
+        format: format.extension().to_owned(),
+    };
+
+    Ok(Derived {
+        image,
+        file,
+    })
+}
+
+/// Routes every previously derived image whose source is part of the provided revision.
+///
+/// This ensures that pages which aren't re-rendered keep working links to their derived images.
+pub fn link_revision(ctx: &Context, rev_id: &RevisionID) -> Result<()> {
+    let conn = ctx.db.get_rw()?;
+
+    let mut query = conn.prepare("
+        SELECT input_files.* FROM input_files
+        JOIN derived_images ON derived_images.id = input_files.id
+        JOIN revision_files ON revision_files.id = derived_images.source
+        WHERE revision_files.revision = ?1
+    ")?;
+
+    for file in query.query_and_then([rev_id.as_ref()], InputFile::from_row)? {
+        link(&conn, rev_id, &file?)?;
+    }
+
+    Ok(())
+}
+
+/// Loads an already-derived image.
+fn load(ctx: &Context, id: &str) -> Result<Option<Derived>> {
+    let conn = ctx.db.get_ro()?;
+
+    let mut query = conn.prepare_cached("
+        SELECT * FROM derived_images
+        WHERE id = ?1
+    ")?;
+
+    let Some(image) = query
+        .query_and_then([id], DerivedImage::from_row)?
+        .next()
+    else {
+        return Ok(None)
+    };
+
+    let file = conn.query_row(
+        "SELECT * FROM input_files WHERE id = ?1",
+        [id],
+        InputFile::from_row
+    )?;
+
+    Ok(Some(Derived {
+        image: image?,
+        file,
+    }))
+}
+
+fn compute_metadata(file: &InputFile) -> Result<ImageMetadata> {
+    let path = format!("{SITE_CACHE_PATH}{}", file.id);
+
+    // Cached files have no extension, so the format has to be guessed from their contents.
+    let reader = ImageReader::open(&path)?.with_guessed_format()?;
+
+    let format = match reader.format() {
+        Some(image::ImageFormat::Jpeg) => "jpeg".to_owned(),
+        Some(image::ImageFormat::Png) => "png".to_owned(),
+        Some(image::ImageFormat::Gif) => "gif".to_owned(),
+        Some(image::ImageFormat::WebP) => "webp".to_owned(),
+        Some(image::ImageFormat::Avif) => "avif".to_owned(),
+        Some(format) => format!("{format:?}").to_lowercase(),
+        None => bail!("Unrecognized image format."),
+    };
+
+    let image = reader.decode()?;
+
+    Ok(ImageMetadata {
+        id: file.id.clone(),
+        width: image.width(),
+        height: image.height(),
+        format,
+        exif: read_exif(Path::new(&path)),
+        dominant_color: dominant_color(&image),
+    })
+}
+
+/// Reads the EXIF fields of an image, keyed by tag name. Images without EXIF data (or with malformed EXIF data)
+/// yield an empty map.
+fn read_exif(path: &Path) -> BTreeMap<String, String> {
+    use exif::{In, Reader, Value};
+
+    let Ok(file) = File::open(path) else {
+        return BTreeMap::new()
+    };
+
+    let Ok(exif) = Reader::new().read_from_container(&mut BufReader::new(file)) else {
+        return BTreeMap::new()
+    };
+
+    exif.fields()
+        // Skips thumbnail fields, as well as any tags that aren't part of the standard.
+        .filter(|field| field.ifd_num == In::PRIMARY && field.tag.description().is_some())
+        .map(|field| {
+            let value = match &field.value {
+                // The displayed form of ASCII values is quoted, which isn't wanted here.
+                Value::Ascii(values) => values
+                    .iter()
+                    .map(|value| String::from_utf8_lossy(value).trim().to_owned())
+                    .join(", "),
+                _ => field.display_value().with_unit(&exif).to_string(),
+            };
+
+            (field.tag.to_string(), value)
+        })
+        .collect()
+}
+
+/// Computes the dominant color of an image, ignoring (mostly) transparent pixels.
+///
+/// Colors are grouped into coarse buckets, and the result is the average color of the most populous bucket.
+fn dominant_color(image: &DynamicImage) -> Option<String> {
+    let thumbnail = image.thumbnail(64, 64).to_rgba8();
+    let mut buckets: BTreeMap<[u8; 3], (u64, [u64; 3])> = BTreeMap::new();
+
+    for pixel in thumbnail.pixels() {
+        let [r, g, b, a] = pixel.0;
+
+        if a < 128 {
+            continue;
+        }
+
+        let (count, sums) = buckets
+            .entry([r >> 4, g >> 4, b >> 4])
+            .or_default();
+
+        *count += 1;
+        sums[0] += r as u64;
+        sums[1] += g as u64;
+        sums[2] += b as u64;
+    }
+
+    // Ties go to the first (darkest) bucket, to keep the result deterministic.
+    let (count, [r, g, b]) = buckets
+        .into_values()
+        .rev()
+        .max_by_key(|(count, _)| *count)?;
+
+    Some(format!("#{:02x}{:02x}{:02x}", r / count, g / count, b / count))
+}
+
+/// Decodes, scales and re-encodes an image, returning the encoded bytes and the new dimensions.
+fn encode(source: &InputFile, width: u32, format: ImageFormat, quality: u8) -> Result<(Vec<u8>, (u32, u32))> {
+    let image = ImageReader::open(format!("{SITE_CACHE_PATH}{}", source.id))?
+        .with_guessed_format()?
+        .decode()?;
+
+    // Images are never scaled up.
+    let image = match width < image.width() {
+        true => image.resize(width, u32::MAX, FilterType::Lanczos3),
+        false => image,
+    };
+
+    let dimensions = (image.width(), image.height());
+    let mut bytes = Vec::new();
+
+    match format {
+        // JPEG has no alpha channel.
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut bytes, quality))?,
+        ImageFormat::Webp => DynamicImage::ImageRgba8(image.to_rgba8())
+            .write_with_encoder(WebPEncoder::new_lossless(&mut bytes))?,
+        ImageFormat::Avif => DynamicImage::ImageRgba8(image.to_rgba8())
+            .write_with_encoder(AvifEncoder::new_with_speed_quality(&mut bytes, AVIF_SPEED, quality))?,
+    }
+
+    Ok((bytes, dimensions))
+}
+
+/// Routes an input file for the provided revision, using its cachebusted route.
+fn link(conn: &rusqlite::Connection, rev_id: &RevisionID, file: &InputFile) -> Result<()> {
+    RevisionFile {
+        id: file.id.clone(),
+        revision: rev_id.to_string(),
+    }.insert_or(conn, OnConflict::Ignore)?;
+
+    Route {
+        id: file.id.clone(),
+        revision: rev_id.to_string(),
+        route: file.cachebust(),
+        kind: RouteKind::RedirectAsset,
+    }.insert_or(conn, OnConflict::Ignore)?;
+
+    Ok(())
+}
+
+/// Computes the path of a derived image, which is only used to give it a meaningful route
+/// (such as `/static/photo-480w.webp?v=...`).
+fn derived_path(source: &Path, width: u32, format: ImageFormat) -> PathBuf {
+    let stem = source
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+
+    source.with_file_name(format!("{stem}-{width}w.{}", format.extension()))
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    #[test]
+    fn colors() {
+        // Three quarters red, one quarter blue - plus a transparent (and so ignored) green stripe.
+        let image = RgbaImage::from_fn(64, 64, |x, y| match (x, y) {
+            (0..=7, _) => Rgba([0, 255, 0, 0]),
+            (_, 0..=15) => Rgba([0, 0, 250, 255]),
+            _ => Rgba([200, 10, 10, 255]),
+        });
+
+        assert_eq!(dominant_color(&DynamicImage::ImageRgba8(image)).as_deref(), Some("#c80a0a"));
+
+        let transparent = RgbaImage::from_pixel(8, 8, Rgba([255, 255, 255, 0]));
+        assert_eq!(dominant_color(&DynamicImage::ImageRgba8(transparent)), None);
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            derived_path(Path::new("assets/photos/cat.jpeg"), 480, ImageFormat::Webp),
+            PathBuf::from("assets/photos/cat-480w.webp")
+        );
+        assert_eq!(
+            derived_path(Path::new("content/post/cat.png"), 1024, ImageFormat::Jpeg),
+            PathBuf::from("content/post/cat-1024w.jpg")
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! Output minification, enabled through the `[build.minify]` section of `ftl.toml`.
+
+use minify_html::Cfg;
+use minify_js::{Session, TopLevelMode};
+
+use crate::prelude::*;
+
+/// Minifies a rendered page, along with any inline CSS and JavaScript.
+///
+/// Whitespace inside of `<pre>` and `<textarea>` elements is left intact.
+pub fn html(html: String) -> String {
+    let cfg = Cfg {
+        minify_css: true,
+        minify_js: true,
+        ..Cfg::spec_compliant()
+    };
+
+    let minified = minify_html::minify(html.as_bytes(), &cfg);
+
+    // The minifier only ever removes (or rewrites) markup, so this should never fail.
+    String::from_utf8(minified).unwrap_or(html)
+}
+
+/// Minifies a JavaScript file, leaving it untouched if it fails to parse.
+pub fn js(source: Vec<u8>, path: &str) -> Vec<u8> {
+    let session = Session::new();
+    let mut minified = Vec::new();
+
+    match minify_js::minify(&session, TopLevelMode::Global, &source, &mut minified) {
+        Ok(()) => minified,
+        Err(err) => {
+            warn!("Could not minify script {path} ({err:?}), leaving it as-is.");
+            source
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn preformatted() {
+        let page = "<html>\n  <body>\n    <p>Some   text</p>\n    <pre><code>fn main() {\n    println!();\n}</code></pre>\n  </body>\n</html>\n";
+        let minified = html(page.to_owned());
+
+        assert!(minified.len() < page.len());
+        assert!(minified.contains("<p>Some text"));
+        assert!(minified.contains("<pre><code>fn main() {\n    println!();\n}</code></pre>"));
+    }
+
+    #[test]
+    fn scripts() {
+        let source = b"// Adds two numbers.\nfunction add(left, right) {\n    return left + right;\n}\n";
+        let minified = String::from_utf8(js(source.to_vec(), "add.js")).unwrap();
+
+        assert!(minified.len() < source.len());
+        assert!(minified.contains("add") && !minified.contains("Adds two numbers"));
+
+        // Unparseable scripts are left alone.
+        assert_eq!(js(b"let = ;".to_vec(), "broken.js"), b"let = ;");
+    }
+}
@@ -1,11 +1,19 @@
+mod compress;
+pub mod feed;
+mod images;
+pub mod minify;
+mod search;
+mod search_json;
+mod sitemap;
 mod stylesheet;
+mod taxonomy;
 mod template;
 
 use crossbeam::channel::Receiver;
 use itertools::Itertools;
 use minijinja::Environment;
 use rayon::prelude::*;
-use template::Ticket;
+use template::{Paginator, Ticket};
 
 use crate::db::*;
 use crate::prelude::*;
@@ -20,6 +28,16 @@ pub struct Renderer {
 
 impl Renderer {
     pub fn new(ctx: &Context, rev_id: Option<&RevisionID>) -> Result<Self> {
+        let renderer = Self::build(ctx, rev_id);
+
+        if renderer.is_err() {
+            Message::BuildFail.print();
+        }
+
+        renderer
+    }
+
+    fn build(ctx: &Context, rev_id: Option<&RevisionID>) -> Result<Self> {
         let rev_id = prepare::prepare(ctx, rev_id)?;
         let env = template::setup_environment(ctx, &rev_id)?;
         let ctx = ctx.clone();
@@ -35,6 +53,17 @@ impl Renderer {
         Ok(new)
     }
 
+    /// Loads a revision that has already been built, without preparing or rendering it.
+    pub fn from_revision(ctx: &Context, rev_id: &RevisionID) -> Result<Self> {
+        let env = template::setup_environment(ctx, rev_id)?;
+
+        Ok(Self {
+            env,
+            ctx: ctx.clone(),
+            rev_id: rev_id.clone(),
+        })
+    }
+
     fn render(&self) -> Result<()> {
         info!("Starting render for revision {}...", self.rev_id);
 
@@ -44,24 +73,44 @@ impl Renderer {
             stylesheet::compile(&self.ctx, &self.rev_id)?;
         }
 
+        images::link_revision(&self.ctx, &self.rev_id)?;
+
         let conn = self.ctx.db.get_rw()?;
         let tickets = self.get_tickets(&conn)?;
         let (handle, tx) = conn.prepare_consumer(consumer_handler);
 
-        tickets
+        // Rendering continues past failed tickets, so that every broken page
+        // can be reported at once.
+        let failures: Vec<_> = tickets
             .into_par_iter()
-            .try_for_each(|ticket| -> Result<_> {
-                let rendered = ticket.build(&self.env)?;
-                
-                tx.send((
-                    ticket,
-                    rendered
-                ))?;
-
-                Ok(())
-            })?;
-
-        // TODO handle the possibility of multiple errors occurring during rendering.
+            .map(|ticket| -> Result<_> {
+                match ticket.build(&self.env) {
+                    Ok(rendered) => {
+                        let output = Output::compressed(
+                            &self.ctx,
+                            ticket.id(),
+                            OutputKind::Page,
+                            rendered
+                        )?;
+
+                        tx.send((
+                            ticket,
+                            output
+                        ))?;
+
+                        Ok(None)
+                    }
+                    Err(error) => Ok(Some(Failure {
+                        path: ticket.page.path.clone(),
+                        template: ticket.page.template.clone(),
+                        error,
+                    }))
+                }
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect();
 
         drop(tx);
 
@@ -69,6 +118,23 @@ impl Renderer {
             .join()
             .expect("Database consumer thread should not panic.")?;
 
+        if !failures.is_empty() {
+            drop(progressor);
+
+            let err = eyre!("{} page(s) failed to render.", failures.len())
+                .note(format!("Revision {} was not stabilized.", self.rev_id))
+                .section(report_failures(failures));
+
+            bail!(err)
+        }
+
+        taxonomy::render(&self.ctx, &self.rev_id, &self.env)?;
+        feed::generate(&self.ctx, &self.rev_id)?;
+        sitemap::generate(&self.ctx, &self.rev_id)?;
+
+        search::index_revision(&self.ctx.db.get_rw()?, &self.rev_id)?;
+        search_json::generate(&self.ctx, &self.rev_id)?;
+
         self.finalize_revision()?;
 
         info!("Finished rendering revison {}.", self.rev_id);
@@ -84,17 +150,19 @@ impl Renderer {
             SELECT pages.* FROM pages
             JOIN revision_files ON revision_files.id = pages.id
             WHERE revision_files.revision = ?1
-            AND NOT EXISTS (
-                SELECT 1 FROM output, dependencies
-                WHERE output.id = pages.id
-                OR dependencies.parent = pages.id
-            )
-            OR EXISTS (
-                SELECT 1 FROM dependencies
-                WHERE dependencies.parent = pages.id
-                AND dependencies.child NOT IN (
-                    SELECT id FROM revision_files
-                    WHERE revision = ?1
+            AND (
+                NOT EXISTS (
+                    SELECT 1 FROM output, dependencies
+                    WHERE output.id = pages.id
+                    OR dependencies.parent = pages.id
+                )
+                OR EXISTS (
+                    SELECT 1 FROM dependencies
+                    WHERE dependencies.parent = pages.id
+                    AND dependencies.child NOT IN (
+                        SELECT id FROM revision_files
+                        WHERE revision = ?1
+                    )
                 )
             )
         ")?;
@@ -104,7 +172,43 @@ impl Renderer {
             WHERE id = ?1
         ")?;
 
-        let tickets: Vec<_> = get_pages
+        // The second and later pages of paginated pages have their own IDs,
+        // and so are checked for output and dependencies separately.
+        let mut get_paginations = conn.prepare("
+            SELECT paginations.* FROM paginations
+            JOIN revision_files ON revision_files.id = paginations.id
+            WHERE revision_files.revision = ?1
+            AND paginations.number > 1
+            AND (
+                NOT EXISTS (
+                    SELECT 1 FROM output_hot
+                    WHERE output_hot.id = paginations.id
+                )
+                OR EXISTS (
+                    SELECT 1 FROM dependencies
+                    WHERE dependencies.parent = paginations.id
+                    AND dependencies.child NOT IN (
+                        SELECT id FROM revision_files
+                        WHERE revision = ?1
+                    )
+                )
+            )
+        ")?;
+
+        let mut get_first_page = conn.prepare("
+            SELECT paginations.* FROM paginations
+            JOIN revision_files ON revision_files.id = paginations.id
+            WHERE revision_files.revision = ?1
+            AND paginations.page = ?2
+            AND paginations.number = 1
+        ")?;
+
+        let mut get_page = conn.prepare("
+            SELECT * FROM pages
+            WHERE id = ?1
+        ")?;
+
+        let mut tickets: Vec<_> = get_pages
             .query_and_then([self.rev_id.as_ref()], Page::from_row)?
             .filter_ok(|page| {
                 if self.ctx.drafts_enabled() {
@@ -117,11 +221,47 @@ impl Renderer {
                 let source = get_source
                     .query_row([&page.id], |row| row.get::<_, String>(0))?;
 
-                Ok(Ticket::new(&self.ctx, &self.rev_id, page, &source))
+                let pagination = match page.paginate {
+                    Some(_) => Some(get_first_page.query_row(
+                        [self.rev_id.as_ref(), &page.id],
+                        Pagination::from_row
+                    )?),
+                    None => None,
+                };
+
+                let mut ticket = Ticket::new(&self.ctx, &self.rev_id, page, &source);
+
+                if let Some(pagination) = pagination {
+                    ticket.paginator = Some(
+                        Paginator::load(&self.ctx, conn, &self.rev_id, &ticket.page, pagination)?
+                    );
+                }
+
+                Ok(ticket)
             })
             .flatten()
             .try_collect()?;
 
+        for pagination in get_paginations.query_and_then([self.rev_id.as_ref()], Pagination::from_row)? {
+            let pagination = pagination?;
+            let page = get_page.query_row([&pagination.page], Page::from_row)?;
+
+            if page.draft && !self.ctx.drafts_enabled() {
+                continue;
+            }
+
+            let source = get_source
+                .query_row([&page.id], |row| row.get::<_, String>(0))?;
+
+            let mut ticket = Ticket::new(&self.ctx, &self.rev_id, page, &source);
+
+            ticket.paginator = Some(
+                Paginator::load(&self.ctx, conn, &self.rev_id, &ticket.page, pagination)?
+            );
+
+            tickets.push(ticket);
+        }
+
         Ok(tickets)
     }
 
@@ -130,15 +270,29 @@ impl Renderer {
 
         conn.prepare("
             UPDATE revisions
-            SET time = datetime('now', 'localtime'),
+            SET time = strftime('%Y-%m-%d %H:%M:%f', 'now', 'localtime'),
                 stable = TRUE
             WHERE revisions.id = ?1
         ")?
         .execute([self.rev_id.as_ref()])?;
 
         conn.prepare("
-            INSERT INTO output (id, revision, kind, content)
-            SELECT output_hot.id, ?1, kind, content FROM output_hot
+            UPDATE revisions
+            SET live = (id = ?1)
+        ")?
+        .execute([self.rev_id.as_ref()])?;
+
+        // Revisions can be re-rendered (such as when the server starts), so any
+        // existing cold output has to be cleared out first to avoid duplicates.
+        conn.prepare("
+            DELETE FROM output
+            WHERE revision = ?1
+        ")?
+        .execute([self.rev_id.as_ref()])?;
+
+        conn.prepare("
+            INSERT INTO output (id, revision, kind, content, gzip, brotli)
+            SELECT output_hot.id, ?1, kind, content, gzip, brotli FROM output_hot
             JOIN revision_files ON revision_files.id = output_hot.id
             WHERE revision_files.revision = ?1
         ")?
@@ -148,7 +302,59 @@ impl Renderer {
     }
 }
 
-fn consumer_handler(conn: &mut Connection, rx: Receiver<(Ticket, String)>) -> Result<()> {
+/// A page that failed to render.
+#[derive(Debug)]
+struct Failure {
+    path: String,
+    template: Option<String>,
+    error: Report,
+}
+
+/// Formats a report of every failed page, grouped by the template it was rendered with.
+fn report_failures(failures: Vec<Failure>) -> String {
+    use std::collections::BTreeMap;
+    use std::fmt::Write;
+
+    use console::style;
+
+    let mut groups: BTreeMap<String, Vec<Failure>> = BTreeMap::new();
+
+    for failure in failures {
+        let template = failure
+            .template
+            .clone()
+            .unwrap_or_else(|| "ftl_default.html".to_owned());
+
+        groups.entry(template).or_default().push(failure);
+    }
+
+    let mut report = String::new();
+
+    for (template, mut failures) in groups {
+        failures.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let _ = writeln!(
+            report,
+            "{} {template} ({} page(s))",
+            style("Template").bold(),
+            failures.len()
+        );
+
+        for failure in failures {
+            let _ = writeln!(report, "  {} {}", style("x").red().bold(), failure.path);
+
+            for cause in failure.error.chain() {
+                for line in cause.to_string().lines() {
+                    let _ = writeln!(report, "      {line}");
+                }
+            }
+        }
+    }
+
+    report
+}
+
+fn consumer_handler(conn: &mut Connection, rx: Receiver<(Ticket, Output)>) -> Result<()> {
     let txn = conn.transaction()?;
 
     let mut remove_deps = txn.prepare("
@@ -157,25 +363,30 @@ fn consumer_handler(conn: &mut Connection, rx: Receiver<(Ticket, String)>) -> Re
     ")?;
 
     for (ticket, output) in rx {
-        let id = ticket.page.id;
+        let id = ticket.id().to_owned();
         
         remove_deps.execute([&id])?;
 
+        for derived in ticket.derived.into_iter() {
+            derived.register(&txn, &ticket.rev_id)?;
+        }
+
+        for metadata in ticket.metadata.into_iter() {
+            metadata.insert_or(&txn, OnConflict::Ignore)?;
+        }
+
+        // A page may use the same file more than once (for example, by resizing an image and reading its metadata.)
         for (relation, child) in ticket.dependencies.into_iter() {
             Dependency {
                 relation,
                 parent: id.clone(),
                 child,
-            }.insert(&txn)?;
+            }.insert_or(&txn, OnConflict::Ignore)?;
         }
 
-        debug!("{output}");
+        debug!("{}", output.content);
         
-        Output {
-            id: Some(id),
-            kind: OutputKind::Page,
-            content: output
-        }.insert_or(&txn, OnConflict::Replace)?;
+        output.insert_or(&txn, OnConflict::Replace)?;
     }
 
     remove_deps.finalize()?;
@@ -0,0 +1,311 @@
+//! Full-text search over the rendered pages of a revision, backed by SQLite's FTS5 extension.
+
+use std::cell::RefCell;
+
+use itertools::Itertools;
+
+use crate::db::*;
+use crate::prelude::*;
+
+/// Marks the start of a matched term in a snippet. Swapped for `<mark>` after escaping.
+const MARK_START: &str = "\u{2}";
+/// Marks the end of a matched term in a snippet. Swapped for `</mark>` after escaping.
+const MARK_END: &str = "\u{3}";
+
+/// How many tokens long snippets are.
+const SNIPPET_TOKENS: usize = 24;
+
+record! {
+    Name    => Row,
+    id      => String,
+    route   => Option<String>,
+    content => String
+}
+
+/// A page matching a search query.
+#[derive(Debug)]
+pub struct Hit {
+    pub id: String,
+    pub route: Option<String>,
+    pub title: Option<String>,
+    /// An HTML excerpt of the page, with matched terms wrapped in `<mark>` tags.
+    pub snippet: String,
+    /// The BM25 rank of the page - lower is better.
+    pub rank: f64,
+}
+
+/// Indexes the plaintext and attributes of every page rendered for a revision.
+///
+/// Must be run before the revision is finalized, as it reads from hot output.
+pub fn index_revision(conn: &Connection, rev_id: &RevisionID) -> Result<()> {
+    conn.execute("DELETE FROM search_index WHERE revision = ?1", [rev_id.as_ref()])?;
+
+    let mut get_pages = conn.prepare("
+        SELECT output_hot.id, routes.route, output_hot.content FROM output_hot
+        JOIN revision_files
+            ON revision_files.id = output_hot.id
+            AND revision_files.revision = ?1
+        LEFT JOIN routes
+            ON routes.id = output_hot.id
+            AND routes.revision = ?1
+            AND routes.kind = 2
+        WHERE output_hot.kind = 0
+        -- Skips the extra pages of paginated pages, which are just listings.
+        AND output_hot.id IN (
+            SELECT id FROM pages
+        )
+    ")?;
+
+    let mut get_attributes = conn.prepare_cached("
+        SELECT kind, property FROM attributes
+        WHERE id = ?1
+    ")?;
+
+    let mut insert = conn.prepare("
+        INSERT INTO search_index (id, revision, route, title, content, attributes)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+    ")?;
+
+    for row in get_pages.query_and_then([rev_id.as_ref()], Row::from_row)? {
+        let row = row?;
+        let content = plaintext(&row.content)?;
+
+        let mut title = None;
+        let mut attributes = Vec::new();
+
+        for attribute in get_attributes.query_map([&row.id], |row| Ok((row.get(0)?, row.get(1)?)))? {
+            let (kind, property): (String, String) = attribute?;
+
+            match kind.as_str() {
+                "title" => title = Some(property),
+                _ => attributes.push(property)
+            }
+        }
+
+        insert.execute(rusqlite::params![
+            row.id,
+            rev_id.as_ref(),
+            row.route,
+            title,
+            content,
+            attributes.join(" ")
+        ])?;
+    }
+
+    Ok(())
+}
+
+/// Searches the pages of a revision, returning up to `limit` hits ordered by relevance.
+/// 
+/// Each whitespace-separated term in the query must appear in a page for it to match,
+/// with the final term matched as a prefix (so that results can be shown as the user types.)
+pub fn search(conn: &Connection, rev_id: &RevisionID, query: &str, limit: usize) -> Result<Vec<Hit>> {
+    let Some(query) = to_fts_query(query) else {
+        return Ok(Vec::new())
+    };
+
+    let mut search = conn.prepare_cached("
+        SELECT id, route, title, snippet(search_index, -1, ?3, ?4, '…', ?5), rank
+        FROM search_index
+        WHERE search_index MATCH ?1
+        AND revision = ?2
+        ORDER BY rank
+        LIMIT ?6
+    ")?;
+
+    let parameters = rusqlite::params![
+        query,
+        rev_id.as_ref(),
+        MARK_START,
+        MARK_END,
+        SNIPPET_TOKENS,
+        limit
+    ];
+
+    let hits = search
+        .query_map(parameters, |row| {
+            let snippet: String = row.get(3)?;
+
+            Ok(Hit {
+                id: row.get(0)?,
+                route: row.get(1)?,
+                title: row.get(2)?,
+                snippet: escape(&snippet)
+                    .replace(MARK_START, "<mark>")
+                    .replace(MARK_END, "</mark>"),
+                rank: row.get(4)?
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+
+    Ok(hits)
+}
+
+/// Converts user input into an FTS5 query, quoting every term so that
+/// FTS5 syntax (such as `-` or `"`) is never interpreted.
+fn to_fts_query(query: &str) -> Option<String> {
+    let terms: Vec<_> = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect();
+
+    match terms.is_empty() {
+        true => None,
+        false => Some(terms.join(" ") + "*")
+    }
+}
+
+/// Extracts the text content of an HTML document, skipping anything that isn't displayed.
+fn plaintext(html: &str) -> Result<String> {
+    use lol_html::{element, rewrite_str, text, RewriteStrSettings};
+
+    // Text handlers still see the contents of removed elements, so they're stripped in a separate pass.
+    let html = rewrite_str(html, RewriteStrSettings {
+        element_content_handlers: vec![
+            element!("head, script, style, template, noscript", |el| {
+                el.remove();
+                Ok(())
+            })
+        ],
+        ..RewriteStrSettings::default()
+    })?;
+
+    let text = RefCell::new(String::new());
+
+    rewrite_str(&html, RewriteStrSettings {
+        element_content_handlers: vec![
+            text!("*", |chunk| {
+                let mut text = text.borrow_mut();
+                text.push_str(chunk.as_str());
+
+                if chunk.last_in_text_node() {
+                    text.push(' ');
+                }
+
+                Ok(())
+            })
+        ],
+        ..RewriteStrSettings::default()
+    })?;
+
+    let text = unescape(&text.into_inner());
+
+    Ok(text.split_whitespace().join(" "))
+}
+
+/// Decodes the character references most likely to appear in rendered Markdown.
+fn unescape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest.find(';').and_then(|end| {
+            let decoded = match &rest[1..end] {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => ' ',
+                reference => {
+                    let code = match reference.strip_prefix("#x").or_else(|| reference.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => reference.strip_prefix('#')?.parse().ok()?,
+                    };
+
+                    char::from_u32(code)?
+                }
+            };
+
+            Some((decoded, end))
+        });
+
+        match decoded {
+            Some((decoded, end)) => {
+                output.push(decoded);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+fn escape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+
+    for char in text.chars() {
+        match char {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#x27;"),
+            _ => output.push(char)
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn extraction() {
+        let html = "
+            <html><head><title>Ignored</title><style>p { color: red; }</style></head>
+            <body><h1>Fish &amp; Chips</h1><p>It&#39;s <em>very</em>&nbsp;good.</p>
+            <script>alert('no');</script></body></html>
+        ";
+
+        assert_eq!(plaintext(html).unwrap(), "Fish & Chips It's very good.");
+        assert_eq!(unescape("a &bogus; &#x41; & b"), "a &bogus; A & b");
+    }
+
+    #[test]
+    fn queries() {
+        assert_eq!(to_fts_query("  "), None);
+        assert_eq!(to_fts_query("rust web"), Some("\"rust\" \"web\"*".to_owned()));
+        assert_eq!(to_fts_query("-\"x"), Some("\"-\"\"x\"*".to_owned()));
+    }
+
+    #[test]
+    fn index_and_search() {
+        let conn = Connection::open(IN_MEMORY).unwrap();
+        conn.execute_batch(PRIME_UP).unwrap();
+        conn.execute_batch("PRAGMA foreign_keys = OFF;").unwrap();
+
+        let rev_id = RevisionID::from("rev");
+
+        conn.execute_batch("
+            INSERT INTO pages (id, path) VALUES ('a', 'content/a.md'), ('b', 'content/b.md');
+            INSERT INTO revision_files VALUES ('a', 'rev'), ('b', 'rev');
+            INSERT INTO output_hot (id, kind, content) VALUES
+                ('a', 0, '<p>Immutable Linux <b>distributions</b> &lt;3</p>'),
+                ('b', 0, '<p>Nothing to see here.</p>');
+            INSERT INTO routes VALUES ('a', 'rev', '/silverblue', 2);
+            INSERT INTO attributes VALUES ('a', 'title', 'Silverblue'), ('a', 'tags', 'linux');
+        ").unwrap();
+
+        index_revision(&conn, &rev_id).unwrap();
+
+        let hits = search(&conn, &rev_id, "distrib", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].route.as_deref(), Some("/silverblue"));
+        assert_eq!(hits[0].title.as_deref(), Some("Silverblue"));
+        assert_eq!(hits[0].snippet, "Immutable Linux <mark>distributions</mark> &lt;3");
+
+        assert_eq!(search(&conn, &rev_id, "linux", 10).unwrap().len(), 1);
+        assert!(search(&conn, &rev_id, "\"unbalanced", 10).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+//! Static JSON search index, for client-side search on sites that can't run hooks (such as exported ones.)
+
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::db::*;
+use crate::prelude::*;
+
+/// How many words of a page's text are used as its summary, if it lacks a summary attribute.
+const SUMMARY_WORDS: usize = 30;
+
+#[derive(Debug, Serialize)]
+struct Index {
+    pages: Vec<Entry>,
+    /// Maps each token to the (sorted) indices of the pages containing it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<BTreeMap<String, Vec<usize>>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Entry {
+    title: Option<String>,
+    route: Option<String>,
+    summary: String,
+    /// The unique, lowercase tokens of the page's title and text, space-separated.
+    text: String,
+}
+
+record! {
+    Name    => Row,
+    id      => String,
+    route   => Option<String>,
+    title   => Option<String>,
+    content => String,
+    summary => Option<String>
+}
+
+/// The route that templates link to the search index with.
+///
+/// The index itself is routed (and cachebusted) by the hash of its contents, which isn't known until
+/// every page has been rendered. This route redirects to it, so that linking to the index doesn't make
+/// a page depend on every other page.
+pub const ROUTE: &str = "/static/search.json";
+
+/// Builds the search index for a revision from its full-text search rows (if enabled), storing it as an output
+/// routed to `/static/search.json?v=...`, with [`ROUTE`] redirecting to it.
+///
+/// Must be run after the revision's pages have been indexed for full-text search.
+pub fn generate(ctx: &Context, rev_id: &RevisionID) -> Result<()> {
+    let conn = ctx.db.get_rw()?;
+
+    // The index is regenerated on every build, so any previous version has to be unrouted.
+    conn.execute("
+        DELETE FROM routes
+        WHERE revision = ?1
+        AND kind IN (?2, ?3)
+    ", params![rev_id.as_ref(), RouteKind::SearchIndex, RouteKind::RedirectSearchIndex])?;
+
+    if !ctx.build.search_index {
+        return Ok(())
+    }
+
+    info!("Generating search index for revision {rev_id}...");
+
+    let mut get_pages = conn.prepare("
+        SELECT search_index.id, route, title, content, attributes.property AS summary
+        FROM search_index
+        LEFT JOIN attributes
+            ON attributes.id = search_index.id
+            AND attributes.kind = 'summary'
+        WHERE search_index.revision = ?1
+        ORDER BY route
+    ")?;
+
+    let pages: Vec<Entry> = get_pages
+        .query_and_then([rev_id.as_ref()], Row::from_row)?
+        .map_ok(|row| {
+            let summary = row.summary.unwrap_or_else(|| {
+                row.content
+                    .split_whitespace()
+                    .take(SUMMARY_WORDS)
+                    .join(" ")
+            });
+
+            let text = tokenize(row.title.as_deref().unwrap_or_default())
+                .chain(tokenize(&row.content))
+                .unique()
+                .join(" ");
+
+            Entry {
+                title: row.title,
+                route: row.route,
+                summary,
+                text,
+            }
+        })
+        .try_collect()?;
+
+    let index = ctx.build.search_index_inverted.then(|| {
+        let mut index: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+
+        for (i, page) in pages.iter().enumerate() {
+            for token in page.text.split(' ').filter(|token| !token.is_empty()) {
+                index.entry(token.to_owned()).or_default().push(i);
+            }
+        }
+
+        index
+    });
+
+    let json = serde_json::to_string(&Index { pages, index })?;
+    let id = format!("{:016x}", seahash::hash(json.as_bytes()));
+
+    // Cachebusted routes share their path with the stable route (and are exported to the same file),
+    // so they conflict too.
+    let mut conflicts = conn.prepare("
+        SELECT NULL FROM routes
+        WHERE revision = ?2
+        AND (route = ?1 OR substr(route, 1, length(?1) + 1) = ?1 || '?')
+    ")?;
+
+    if conflicts.exists([ROUTE, rev_id.as_ref()])? {
+        let err = eyre!("Search index route \"{ROUTE}\" conflicts with an existing route.")
+            .suggestion("Move or rename the asset at this route, or disable the search index.");
+
+        bail!(err)
+    }
+
+    let routes = [
+        (format!("{ROUTE}?v={id}"), RouteKind::SearchIndex),
+        (ROUTE.to_owned(), RouteKind::RedirectSearchIndex),
+    ];
+
+    for (route, kind) in routes {
+        Route {
+            id: id.clone(),
+            revision: rev_id.to_string(),
+            route,
+            kind,
+        }.insert_or(&conn, OnConflict::Ignore)?;
+    }
+
+    RevisionFile {
+        id: id.clone(),
+        revision: rev_id.to_string(),
+    }.insert_or(&conn, OnConflict::Ignore)?;
+
+    let mut exists = conn.prepare("
+        SELECT NULL FROM output_hot
+        WHERE id = ?1
+    ")?;
+
+    if !exists.exists([&id])? {
+        Output::compressed(ctx, id, OutputKind::SearchIndex, json)?
+            .insert_or(&conn, OnConflict::Replace)?;
+    }
+
+    info!("Search index generation complete.");
+    Ok(())
+}
+
+/// Splits text into lowercase alphanumeric tokens.
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text
+        .split(|char: char| !char.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tokens() {
+        let tokens: Vec<_> = tokenize("Fedora Silverblue: a review (2022) - I've").collect();
+        assert_eq!(tokens, ["fedora", "silverblue", "a", "review", "2022", "i", "ve"]);
+    }
+}
@@ -0,0 +1,188 @@
+//! `sitemap.xml` and `robots.txt`, for search engines.
+
+use std::fmt::Write;
+
+use itertools::Itertools;
+
+use super::feed::{escape, parse_date};
+use crate::db::*;
+use crate::prelude::*;
+
+/// The most URLs a single sitemap may list. Larger sites are split into several sitemaps, listed by a sitemap index.
+const MAX_URLS: usize = 50_000;
+
+record! {
+    Name    => Row,
+    route   => String,
+    date    => Option<String>
+}
+
+/// Generates the sitemap (and `robots.txt`) for the provided revision, if enabled.
+pub fn generate(ctx: &Context, rev_id: &RevisionID) -> Result<()> {
+    let conn = ctx.db.get_rw()?;
+
+    // The sitemap is regenerated on every build, so any previous version has to be unrouted.
+    conn.execute("
+        DELETE FROM routes
+        WHERE revision = ?1
+        AND kind IN (?2, ?3)
+    ", params![rev_id.as_ref(), RouteKind::Sitemap, RouteKind::Robots])?;
+
+    if !ctx.sitemap.enabled {
+        return Ok(())
+    }
+
+    info!("Generating sitemap for revision {rev_id}...");
+
+    let root = ctx.root_url.trim_end_matches('/');
+
+    // The extra pages of paginated pages follow the paginated page, while other generated pages
+    // (such as taxonomy listings) aren't in the pages table, and are always listed.
+    let mut get_urls = conn.prepare("
+        SELECT routes.route, (
+            SELECT MAX(property) FROM attributes
+            WHERE attributes.id = routes.id
+            AND attributes.kind = ?2
+        ) AS date
+        FROM routes
+        LEFT JOIN paginations ON paginations.id = routes.id
+        LEFT JOIN pages ON pages.id = COALESCE(paginations.page, routes.id)
+        WHERE routes.revision = ?1
+        AND routes.kind = 2
+        AND (pages.id IS NULL OR (pages.draft = FALSE AND pages.sitemap != FALSE))
+        ORDER BY routes.route
+    ")?;
+
+    let urls: Vec<_> = get_urls
+        .query_and_then(params![rev_id.as_ref(), &ctx.sitemap.date_attribute], Row::from_row)?
+        .map_ok(|row| {
+            let lastmod = row.date.as_deref().and_then(|date| {
+                let parsed = parse_date(date);
+
+                if parsed.is_none() {
+                    warn!("Ignoring invalid sitemap date \"{date}\" for route {}.", row.route);
+                }
+
+                parsed
+            });
+
+            url(&format!("{root}{}", row.route), lastmod.map(|date| date.to_rfc3339()))
+        })
+        .try_collect()?;
+
+    match urls.len() {
+        len if len <= MAX_URLS => emit(ctx, &conn, rev_id, "/sitemap.xml", RouteKind::Sitemap, urlset(&urls))?,
+        _ => {
+            let mut sitemaps = Vec::new();
+
+            for (i, chunk) in urls.chunks(MAX_URLS).enumerate() {
+                let route = format!("/sitemap-{}.xml", i + 1);
+
+                emit(ctx, &conn, rev_id, &route, RouteKind::Sitemap, urlset(chunk))?;
+                sitemaps.push(format!("{root}{route}"));
+            }
+
+            emit(ctx, &conn, rev_id, "/sitemap.xml", RouteKind::Sitemap, sitemap_index(&sitemaps))?;
+        }
+    }
+
+    if ctx.sitemap.robots {
+        let robots = format!(
+            "{}\n\nSitemap: {root}/sitemap.xml\n",
+            ctx.sitemap.robots_rules.trim_end()
+        );
+
+        emit(ctx, &conn, rev_id, "/robots.txt", RouteKind::Robots, robots)?;
+    }
+
+    Ok(())
+}
+
+/// Formats a single `<url>` entry.
+fn url(loc: &str, lastmod: Option<String>) -> String {
+    match lastmod {
+        Some(lastmod) => format!("<url><loc>{}</loc><lastmod>{lastmod}</lastmod></url>", escape(loc)),
+        None => format!("<url><loc>{}</loc></url>", escape(loc)),
+    }
+}
+
+fn urlset(urls: &[String]) -> String {
+    let mut xml = String::new();
+
+    // Unwrap justification: writing to a String can't fail.
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(xml, r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#).unwrap();
+
+    for url in urls {
+        writeln!(xml, "{url}").unwrap();
+    }
+
+    writeln!(xml, "</urlset>").unwrap();
+    xml
+}
+
+fn sitemap_index(sitemaps: &[String]) -> String {
+    let mut xml = String::new();
+
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(xml, r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#).unwrap();
+
+    for sitemap in sitemaps {
+        writeln!(xml, "<sitemap><loc>{}</loc></sitemap>", escape(sitemap)).unwrap();
+    }
+
+    writeln!(xml, "</sitemapindex>").unwrap();
+    xml
+}
+
+/// Routes a generated file, storing its output if it doesn't already exist.
+fn emit(ctx: &Context, conn: &Connection, rev_id: &RevisionID, route: &str, kind: RouteKind, content: String) -> Result<()> {
+    let id = format!("{:016x}", seahash::hash(content.as_bytes()));
+
+    Route {
+        id: id.clone(),
+        revision: rev_id.to_string(),
+        route: route.to_owned(),
+        kind,
+    }.insert_or(conn, OnConflict::Replace)?;
+
+    RevisionFile {
+        id: id.clone(),
+        revision: rev_id.to_string(),
+    }.insert_or(conn, OnConflict::Ignore)?;
+
+    let mut exists = conn.prepare("
+        SELECT NULL FROM output_hot
+        WHERE id = ?1
+    ")?;
+
+    if !exists.exists([&id])? {
+        let output_kind = match kind {
+            RouteKind::Robots => OutputKind::Robots,
+            _ => OutputKind::Sitemap,
+        };
+
+        Output::compressed(ctx, id, output_kind, content)?
+            .insert_or(conn, OnConflict::Replace)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn entries() {
+        assert_eq!(
+            url("https://ftl.rs/a&b", Some("2023-01-01T00:00:00+00:00".to_owned())),
+            "<url><loc>https://ftl.rs/a&amp;b</loc><lastmod>2023-01-01T00:00:00+00:00</lastmod></url>"
+        );
+
+        let index = sitemap_index(&["https://ftl.rs/sitemap-1.xml".to_owned()]);
+
+        assert!(index.contains("<sitemapindex"));
+        assert!(index.contains("<sitemap><loc>https://ftl.rs/sitemap-1.xml</loc></sitemap>"));
+    }
+}
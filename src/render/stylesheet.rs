@@ -3,7 +3,7 @@ use std::io;
 use std::path::{Path, PathBuf};
 
 use ahash::AHashMap;
-use grass::{Fs, Options};
+use grass::{Fs, Options, OutputStyle};
 use itertools::Itertools;
 
 use crate::db::*;
@@ -81,8 +81,15 @@ pub fn compile(ctx: &Context, rev_id: &RevisionID) -> Result<()> {
         kind: RouteKind::Stylesheet,
     }.insert_or(&conn, OnConflict::Replace)?;
 
+    // The output has to be associated with every revision that uses it (not just the one
+    // it was compiled for) so that it gets copied into the revision when it is finalized.
+    RevisionFile {
+        id: hash.clone(),
+        revision: rev_id.to_string()
+    }.insert_or(&conn, OnConflict::Ignore)?;
+
     let mut query = conn.prepare("
-        SELECT NULL FROM output
+        SELECT NULL FROM output_hot
         WHERE id = ?1
     ")?;
 
@@ -94,7 +101,15 @@ pub fn compile(ctx: &Context, rev_id: &RevisionID) -> Result<()> {
     query.finalize()?;
 
     let fs = MapFs::load(ctx, rev_id)?;
-    let options = Options::default().fs(&fs);
+    let style = match ctx.build.minify.css {
+        true => OutputStyle::Compressed,
+        false => OutputStyle::Expanded,
+    };
+
+    let options = Options::default()
+        .fs(&fs)
+        .style(style);
+
     let path = Path::new("style.scss");
 
     if !fs.is_file(path) {
@@ -106,16 +121,8 @@ pub fn compile(ctx: &Context, rev_id: &RevisionID) -> Result<()> {
 
     let output = grass::from_path(path, &options)?;
 
-    RevisionFile {
-        id: hash.clone(),
-        revision: rev_id.to_string()
-    }.insert_or(&conn, OnConflict::Ignore)?;
-
-    Output {
-        id: hash.into(),
-        kind: OutputKind::Stylesheet,
-        content: output,
-    }.insert_or(&conn, OnConflict::Replace)?;
+    Output::compressed(ctx, hash, OutputKind::Stylesheet, output)?
+        .insert_or(&conn, OnConflict::Replace)?;
 
     Ok(())
 }
@@ -132,13 +139,17 @@ pub fn load_hash(ctx: &Context, rev_id: &RevisionID) -> Result<String> {
         ORDER BY input_files.id
     ")?;
 
-    let hash = query
+    let mut hasher = query
         .query_and_then([rev_id.as_ref()], |row| row.get::<_, String>(0))?
         .fold_ok(seahash::SeaHasher::new(), |mut hasher, id: String| {
             id.hash(&mut hasher);
             hasher
-        })?
-        .finish();
+        })?;
+
+    // Minification changes the output, so it has to change the hash too.
+    ctx.build.minify.css.hash(&mut hasher);
+
+    let hash = hasher.finish();
 
     info!("Stylesheet compilation complete.");
     Ok(format!("{hash:016x}"))
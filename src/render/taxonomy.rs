@@ -0,0 +1,266 @@
+//! Listing pages for the taxonomies declared in `ftl.toml`.
+//!
+//! Taxonomy pages have no source file, so (like the stylesheet) their IDs are hashes of everything that goes into them -
+//! their templates, terms and member pages. A page is only re-rendered when that hash changes.
+
+use std::hash::{Hash, Hasher};
+
+use itertools::Itertools;
+use minijinja::value::Value;
+use minijinja::{context, Environment, Template};
+use seahash::SeaHasher;
+
+use super::minify;
+use super::template::{page_item, WrappedReport as Wrap};
+use crate::db::*;
+use crate::prelude::*;
+
+/// A term of a taxonomy, along with its member pages.
+struct Term {
+    name: String,
+    route: String,
+    /// The IDs of the member pages, in order.
+    ids: Vec<String>,
+    pages: Vec<Value>,
+}
+
+impl Term {
+    fn to_value(&self) -> Value {
+        Value::from_iter([
+            ("name", Value::from(self.name.as_str())),
+            ("slug", Value::from(slug::slugify(&self.name))),
+            ("route", Value::from(self.route.as_str())),
+            ("count", Value::from(self.pages.len())),
+            ("pages", Value::from(self.pages.clone())),
+        ])
+    }
+}
+
+pub fn render(ctx: &Context, rev_id: &RevisionID, env: &Environment) -> Result<()> {
+    for taxonomy in &ctx.taxonomies {
+        render_taxonomy(ctx, rev_id, env, taxonomy)
+            .wrap_err_with(|| format!("Failed to render taxonomy \"{}\".", taxonomy.name))?;
+    }
+
+    Ok(())
+}
+
+fn render_taxonomy(ctx: &Context, rev_id: &RevisionID, env: &Environment, taxonomy: &Taxonomy) -> Result<()> {
+    info!("Rendering taxonomy \"{}\"...", taxonomy.name);
+
+    let conn = ctx.db.get_rw()?;
+    let terms = load_terms(ctx, rev_id, &conn, taxonomy)?;
+    let index_route = format!("/{}", slug::slugify(&taxonomy.name));
+    let index_template = taxonomy
+        .index_template
+        .as_deref()
+        .unwrap_or(&taxonomy.template);
+
+    // Every page shows the full list of terms, so their names and member counts
+    // go into every hash. Changes to the pages of *other* terms don't.
+    let mut base = SeaHasher::new();
+    taxonomy.hash(&mut base);
+
+    // Like source pages, listings have to be re-rendered when minification is toggled.
+    (ctx.build.minify.html, ctx.build.minify.css).hash(&mut base);
+
+    for term in &terms {
+        (&term.name, term.ids.len()).hash(&mut base);
+    }
+
+    let values: Vec<_> = terms
+        .iter()
+        .map(Term::to_value)
+        .collect();
+
+    let index = {
+        let mut hasher = base;
+        template_ids(&conn, index_template)?.hash(&mut hasher);
+
+        for term in &terms {
+            term.ids.hash(&mut hasher);
+        }
+
+        format!("{:016x}", hasher.finish())
+    };
+
+    let context = |term: Option<Value>| context!(
+        taxonomy => context!(
+            name => &taxonomy.name,
+            route => &index_route,
+            terms => &values,
+            term => term,
+        )
+    );
+
+    emit(ctx, &conn, rev_id, &index, &index_route, &env.get_template(index_template)?, context(None))?;
+
+    let template_ids = template_ids(&conn, &taxonomy.template)?;
+    let template = env.get_template(&taxonomy.template)?;
+
+    for (term, value) in terms.iter().zip(&values) {
+        let mut hasher = base;
+        (&template_ids, &term.name, &term.ids).hash(&mut hasher);
+
+        let id = format!("{:016x}", hasher.finish());
+
+        emit(ctx, &conn, rev_id, &id, &term.route, &template, context(Some(value.clone())))
+            .wrap_err_with(|| format!("Failed to render term \"{}\".", term.name))?;
+    }
+
+    Ok(())
+}
+
+/// Loads every term of the taxonomy (in order), along with their member pages.
+fn load_terms(ctx: &Context, rev_id: &RevisionID, conn: &Connection, taxonomy: &Taxonomy) -> Result<Vec<Term>> {
+    let mut get_terms = conn.prepare("
+        SELECT DISTINCT property FROM attributes
+        JOIN revision_files ON revision_files.id = attributes.id
+        JOIN pages ON pages.id = attributes.id
+        WHERE revision_files.revision = ?1
+        AND attributes.kind = ?2
+        AND (pages.draft = FALSE OR ?3)
+        ORDER BY property
+    ")?;
+
+    let names: Vec<String> = get_terms
+        .query_map(
+            params![rev_id.as_ref(), &taxonomy.name, ctx.drafts_enabled()],
+            |row| row.get(0)
+        )?
+        .try_collect()?;
+
+    let mut terms = Vec::with_capacity(names.len());
+
+    for name in names {
+        // Member pages are just the results of an attribute filter.
+        let filter = Paginate {
+            per_page: usize::MAX,
+            query: None,
+            attribute: Some(taxonomy.name.clone()),
+            value: Some(name.clone()),
+            sort_by: taxonomy.sort_by.clone(),
+            reverse: taxonomy.reverse,
+        };
+
+        let mut stmt = filter.statement(conn, "", rev_id.as_ref(), ctx.drafts_enabled(), None)?;
+        let mut rows = stmt.raw_query();
+
+        let mut ids = Vec::new();
+        let mut pages = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            ids.push(row.get("id")?);
+            pages.push(page_item(row)?);
+        }
+
+        terms.push(Term {
+            route: format!("/{}/{}", slug::slugify(&taxonomy.name), slug::slugify(&name)),
+            name,
+            ids,
+            pages,
+        })
+    }
+
+    if let Some(term) = terms.iter().duplicates_by(|term| &term.route).next() {
+        let err = eyre!("More than one term of the taxonomy has the route \"{}\".", term.route)
+            .note("Terms are routed by their slug, so terms that differ only in case or punctuation collide.");
+
+        bail!(err)
+    }
+
+    Ok(terms)
+}
+
+/// Loads the IDs of the provided template and everything it (transitively) depends on.
+fn template_ids(conn: &Connection, template: &str) -> Result<Vec<String>> {
+    let mut query = conn.prepare("
+        SELECT child FROM dependencies
+        WHERE parent = ?1
+        AND relation = 0
+        ORDER BY child
+    ")?;
+
+    let ids: Vec<String> = query
+        .query_map([template], |row| row.get(0))?
+        .try_collect()?;
+
+    if ids.is_empty() {
+        let err = eyre!("Taxonomy template \"{template}\" could not be found.")
+            .suggestion("Double check the taxonomy's configuration in ftl.toml for spelling and path mistakes.");
+
+        bail!(err)
+    }
+
+    Ok(ids)
+}
+
+/// Routes a taxonomy page, rendering it if its output doesn't already exist.
+fn emit(
+    ctx: &Context,
+    conn: &Connection,
+    rev_id: &RevisionID,
+    id: &str,
+    route: &str,
+    template: &Template,
+    context: Value
+) -> Result<()> {
+    // Taxonomies are configured outside of the revision, so a re-rendered revision
+    // may have stale taxonomy routes (that don't belong to a page) left over.
+    conn.execute("
+        DELETE FROM routes
+        WHERE route = ?1
+        AND revision = ?2
+        AND kind = 2
+        AND id NOT IN (SELECT id FROM pages)
+        AND id NOT IN (SELECT id FROM paginations)
+    ", [route, rev_id.as_ref()])?;
+
+    let mut conflicts = conn.prepare("
+        SELECT NULL FROM routes
+        WHERE route = ?1
+        AND revision = ?2
+    ")?;
+
+    if conflicts.exists([route, rev_id.as_ref()])? {
+        let err = eyre!("Taxonomy route \"{route}\" conflicts with an existing route.")
+            .suggestion("Move or rename the page (or asset) at this route.");
+
+        bail!(err)
+    }
+
+    Route {
+        id: id.to_owned(),
+        revision: rev_id.to_string(),
+        route: route.to_owned(),
+        kind: RouteKind::Page,
+    }.insert_or(conn, OnConflict::Ignore)?;
+
+    RevisionFile {
+        id: id.to_owned(),
+        revision: rev_id.to_string(),
+    }.insert_or(conn, OnConflict::Ignore)?;
+
+    let mut exists = conn.prepare("
+        SELECT NULL FROM output_hot
+        WHERE id = ?1
+    ")?;
+
+    if exists.exists([id])? {
+        return Ok(());
+    }
+
+    let rendered = template
+        .render(context)
+        .map_err(Wrap::flatten)?;
+
+    let rendered = match ctx.build.minify.html {
+        true => minify::html(rendered),
+        false => rendered,
+    };
+
+    Output::compressed(ctx, id, OutputKind::Page, rendered)?
+        .insert_or(conn, OnConflict::Replace)?;
+
+    Ok(())
+}
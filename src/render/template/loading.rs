@@ -69,11 +69,11 @@ fn compute_dependencies(conn: &mut Connection, templates: &[Row]) -> Result<()>
     // Open and setup an in-memory database for use as our working space.
     txn.execute_batch(AUX_UP)?;
 
-    // Purge old template dependencies from the on-disk database.
+    // Purge old template dependencies (relation 0, i.e. `Relation::Intertemplate`) from the on-disk database.
     //
     // We *could* differentiate them based on revision, but that would
     // be pointless since we only care about the current one.
-    txn.execute("DELETE FROM dependencies WHERE relation = 1;", [])?;
+    txn.execute("DELETE FROM dependencies WHERE relation = 0;", [])?;
 
     // Prepare all the necessary statements for dependency mapping.
     let insert_template = "
@@ -104,7 +104,7 @@ fn compute_dependencies(conn: &mut Connection, templates: &[Row]) -> Result<()>
             )
 
         INSERT OR IGNORE INTO dependencies
-        SELECT 1, template_name.name, transitives.id
+        SELECT 0, template_name.name, transitives.id
         FROM template_name, transitives;
     ";
 
@@ -209,7 +209,7 @@ mod test {
         let mut query = conn.prepare("
             SELECT parent AS name, child AS id FROM dependencies
             WHERE parent = ?1
-            AND relation = 1
+            AND relation = 0
         ")?;
 
         let alpha_deps: Vec<Template> = query
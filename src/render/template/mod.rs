@@ -10,7 +10,7 @@ use minijinja::{context, Environment, State};
 pub use error::*;
 pub use objects::*;
 
-use super::stylesheet;
+use super::{search_json, stylesheet};
 
 use crate::db::*;
 use crate::prelude::*;
@@ -58,6 +58,10 @@ pub fn register_routines(
         });
     }
     
+    if ctx.build.search_index {
+        env.add_function("search_index_path", || search_json::ROUTE);
+    }
+
     env.add_function("eval", eval);
     env.add_function("raise", raise);
     env.add_function("dbg", dbg);
@@ -83,6 +87,14 @@ pub fn register_routines(
     let db = DbHandle::new(ctx, rev_id);
     env.add_filter("query", move |sql, params| db.query(sql, params));
 
+    let db = DbHandle::new(ctx, rev_id);
+    env.add_function("search", move |query, limit| db.search(query, limit));
+
+    let db = DbHandle::new(ctx, rev_id);
+    env.add_function("resize", move |state: &State, path, width, format| {
+        db.resize(state, path, width, format).map_err(Wrap::wrap)
+    });
+
     Ok(())
 }
 
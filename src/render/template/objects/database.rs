@@ -3,6 +3,7 @@ use std::sync::Arc;
 
 use minijinja::value::*;
 use minijinja::State;
+use once_cell::sync::OnceCell;
 use rusqlite::{
     Row,
     Params,
@@ -43,7 +44,59 @@ impl DbHandle {
         .map_err(Wrap::wrap)
     }
 
+    /// Searches the pages of the revision, returning up to `limit` (default 10) ranked hits.
+    pub fn search(&self, query: String, limit: Option<usize>) -> MJResult {
+        let conn = self.pool.get().map_err(Wrap::wrap)?;
+
+        let hits = crate::render::search::search(
+            &conn,
+            &self.rev_id,
+            &query,
+            limit.unwrap_or(10)
+        ).map_err(Wrap::wrap)?;
+
+        let hits = hits
+            .into_iter()
+            .map(|hit| {
+                Value::from_iter([
+                    ("id", Value::from(hit.id)),
+                    ("route", Value::from_serializable(&hit.route)),
+                    ("title", Value::from_serializable(&hit.title)),
+                    ("snippet", Value::from_safe_string(hit.snippet)),
+                    ("rank", Value::from(hit.rank)),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Value::from(hits))
+    }
+
     pub fn get_resource(&self, state: &State, path: String) -> Result<Value> {
+        let file = self.resolve(state, &path)?;
+
+        Ok(Resource {
+            inner: Value::from_serializable(&file),
+            base: file,
+            ctx: Arc::clone(&self.ctx),
+            rev_id: self.rev_id.clone(),
+            metadata: OnceCell::new(),
+        })
+        .map(Value::from_object)
+    }
+
+    /// Derives a copy of the image at the provided path, scaled down to the given width
+    /// and converted to the given format (see [`Image::derive`].)
+    pub fn resize(&self, state: &State, path: String, width: u32, format: Option<String>) -> Result<Value> {
+        let file = self.resolve(state, &path)?;
+        Image::derive(state, &self.ctx, &self.rev_id, &file, width, format)
+    }
+}
+
+// Internal methods (kept separate for readability/organization.)
+impl DbHandle {
+    /// Resolves a path to a file in the revision - relative to the page being rendered (if any), then the
+    /// asset and content directories, and finally the site root.
+    fn resolve(&self, state: &State, path: &str) -> Result<InputFile> {
         let conn = self.pool.get()?;
         let rev_id = self.rev_id.as_ref();
         let mut lookup_targets = Vec::with_capacity(4);
@@ -83,18 +136,9 @@ impl DbHandle {
             _ => bail!("Could not resolve resource at path \"{path}\".")
         };
 
-        Ok(Resource {
-            inner: Value::from_serializable(&file),
-            base: file,
-            ctx: Arc::clone(&self.ctx),
-            rev_id: self.rev_id.clone(),
-        })
-        .map(Value::from_object)
+        Ok(file)
     }
-}
 
-// Internal methods (kept separate for readability/organization.)
-impl DbHandle {
     /// Query the database using the provided SQL and parameters.
     ///
     /// Parameters must be of the following form:
@@ -210,10 +254,18 @@ impl Object for DbHandle {
                 let (sql, params) = from_args(args)?;
                 self.query(sql, params)
             }
+            "search" => {
+                let (query, limit) = from_args(args)?;
+                self.search(query, limit)
+            }
             "get_resource" => {
                 let (path,) = from_args(args)?;
                 self.get_resource(state, path).map_err(Wrap::wrap)
             }
+            "resize" => {
+                let (path, width, format) = from_args(args)?;
+                self.resize(state, path, width, format).map_err(Wrap::wrap)
+            }
             _ => Err(MJError::new(
                 MJErrorKind::UnknownMethod,
                 format!("object has no method named {name}"),
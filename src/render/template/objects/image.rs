@@ -0,0 +1,89 @@
+use minijinja::value::*;
+use minijinja::State;
+
+use super::*;
+use crate::db::*;
+use crate::prelude::*;
+use crate::render::images;
+
+/// An image derived from an asset, as returned by `resize`.
+///
+/// Displays as its route, so it can be used directly as an `src`.
+#[derive(Debug)]
+pub struct Image {
+    pub route  : String,
+    pub width  : u32,
+    pub height : u32,
+    pub format : String,
+}
+
+impl Image {
+    /// Derives an image from the provided file, converting it to the provided format (or the configured default.)
+    ///
+    /// While rendering a page, the image is registered along with the page's output, and the page is
+    /// made to depend on the source file. Otherwise, it is registered immediately.
+    pub fn derive(
+        state: &State,
+        ctx: &Context,
+        rev_id: &RevisionID,
+        source: &InputFile,
+        width: u32,
+        format: Option<String>
+    ) -> Result<Value> {
+        let format = match format {
+            Some(format) => format.parse()?,
+            None => ctx.build.images.format,
+        };
+
+        let derived = images::derive(ctx, source, width, format)?;
+
+        let queued = try_with_ticket(state, |ticket| -> Result<()> {
+            ticket.register_dependency(Relation::PageAsset, &source.id)?;
+            ticket.derived.push(derived.clone());
+            Ok(())
+        });
+
+        match queued {
+            Some(result) => result?,
+            None => {
+                let conn = ctx.db.get_rw()?;
+                derived.register(&conn, rev_id)?
+            }
+        }
+
+        Ok(Value::from_object(Self {
+            route: derived.route(),
+            width: derived.image.width,
+            height: derived.image.height,
+            format: derived.image.format,
+        }))
+    }
+}
+
+impl std::fmt::Display for Image {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.route)
+    }
+}
+
+impl Object for Image {
+    fn kind(&self) -> ObjectKind<'_> {
+        ObjectKind::Struct(self)
+    }
+}
+
+impl StructObject for Image {
+    fn get_field(&self, name: &str) -> Option<Value> {
+        match name {
+            "route" => Some(Value::from(self.route.as_str())),
+            "width" => Some(Value::from(self.width)),
+            "height" => Some(Value::from(self.height)),
+            "format" => Some(Value::from(self.format.as_str())),
+            _ => None,
+        }
+    }
+
+    fn static_fields(&self) -> Option<&'static [&'static str]> {
+        Some(&["route", "width", "height", "format"])
+    }
+}
@@ -1,8 +1,12 @@
 mod database;
+mod image;
+mod paginator;
 mod resource;
 mod ticket;
 
 pub use database::*;
+pub use image::*;
+pub use paginator::*;
 use minijinja::State;
 pub use resource::*;
 pub use ticket::*;
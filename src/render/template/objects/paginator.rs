@@ -0,0 +1,100 @@
+use minijinja::value::*;
+use serde::Serialize;
+
+use super::*;
+use crate::db::*;
+use crate::prelude::*;
+
+/// One page of a paginated page, exposed to its template as the `paginator` variable.
+#[derive(Debug)]
+pub struct Paginator {
+    /// The ID of the page (see [`Pagination`].)
+    pub id     : String,
+    /// The (1-based) page number.
+    pub number : i64,
+    pub inner  : Value,
+}
+
+#[derive(Serialize)]
+struct Item<'a> {
+    #[serde(flatten)]
+    page  : &'a Page,
+    route : Option<String>,
+}
+
+impl Paginator {
+    /// Loads the items and links of the provided page of a paginated page.
+    pub fn load(
+        ctx: &Context,
+        conn: &Connection,
+        rev_id: &RevisionID,
+        page: &Page,
+        pagination: Pagination
+    ) -> Result<Self> {
+        // Unwrap justification: only paginated pages have paginations.
+        let paginate = page.paginate.as_ref().unwrap();
+
+        let base: String = conn.query_row("
+            SELECT route FROM routes
+            WHERE id = ?1
+            AND revision = ?2
+            AND kind = 2
+        ", [page.id.as_str(), rev_id.as_ref()], |row| row.get(0))?;
+
+        let mut stmt = paginate.statement(
+            conn,
+            &page.id,
+            rev_id.as_ref(),
+            ctx.drafts_enabled(),
+            Some(pagination.number as usize)
+        )?;
+
+        let mut rows = stmt.raw_query();
+        let mut items = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            // Query results are passed through as-is, while attribute-filtered
+            // items look like any other page (plus their route.)
+            let item = match paginate.query {
+                Some(_) => Value::from_struct_object(ValueMap::from_row(row)?),
+                None => page_item(row)?,
+            };
+
+            items.push(item);
+        }
+
+        let number = pagination.number;
+        let total = pagination.total;
+
+        let link = |number: i64| {
+            (1..=total)
+                .contains(&number)
+                .then(|| Pagination::route(&base, number))
+        };
+
+        let inner = Value::from_iter([
+            ("items", Value::from(items)),
+            ("current", Value::from(number)),
+            ("total", Value::from(total)),
+            ("per_page", Value::from(paginate.per_page)),
+            ("first", Value::from(Pagination::route(&base, 1))),
+            ("last", Value::from(Pagination::route(&base, total))),
+            ("previous", Value::from_serializable(&link(number - 1))),
+            ("next", Value::from_serializable(&link(number + 1))),
+        ]);
+
+        Ok(Self {
+            id: pagination.id,
+            number,
+            inner,
+        })
+    }
+}
+
+/// Converts a row of pages (plus their routes), as selected by an attribute-filtered [`Paginate`], into a template value.
+pub fn page_item(row: &rusqlite::Row) -> Result<Value> {
+    Ok(Value::from_serializable(&Item {
+        page: &Page::from_row(row)?,
+        route: row.get("route")?,
+    }))
+}
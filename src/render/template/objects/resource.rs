@@ -1,8 +1,10 @@
 use minijinja::value::*;
+use once_cell::sync::OnceCell;
 
 use super::*;
 use crate::db::*;
 use crate::prelude::*;
+use crate::render::images;
 
 /// A resource known to FTL, such as an image or page. Acquired inside the engine
 /// through the [`DbHandle::get_resource`] method.
@@ -15,6 +17,8 @@ pub struct Resource {
     pub inner: Value,
     pub ctx: Context,
     pub rev_id: RevisionID,
+    /// The resource's image metadata, if it is an image and has been requested.
+    pub metadata: OnceCell<ImageMetadata>,
 }
 
 impl Resource {
@@ -93,6 +97,72 @@ impl Resource {
         Ok(value)
     }
 
+    fn resize(&self, state: &State, width: u32, format: Option<String>) -> Result<Value> {
+        Image::derive(state, &self.ctx, &self.rev_id, &self.base, width, format)
+    }
+
+    /// Loads (or computes) the resource's image metadata, or returns `None` if the resource isn't an image.
+    ///
+    /// While rendering a page, newly computed metadata is cached along with the page's output,
+    /// and the page is made to depend on the resource. Otherwise, it is cached immediately.
+    fn metadata(&self, state: &State) -> Result<Option<&ImageMetadata>> {
+        if !images::is_image(&self.base) {
+            return Ok(None);
+        }
+
+        self.metadata.get_or_try_init(|| {
+            let metadata = images::metadata(&self.ctx, &self.base)?;
+
+            let queued = try_with_ticket(state, |ticket| -> Result<()> {
+                ticket.register_dependency(Relation::PageAsset, &self.base.id)?;
+                ticket.metadata.push(metadata.clone());
+                Ok(())
+            });
+
+            match queued {
+                Some(result) => result?,
+                None => {
+                    let conn = self.ctx.db.get_rw()?;
+                    metadata.insert_or(&conn, OnConflict::Ignore)?;
+                }
+            }
+
+            Ok(metadata)
+        })
+        .map(Some)
+    }
+
+    fn dimensions(&self, state: &State) -> Result<Value> {
+        Ok(match self.metadata(state)? {
+            Some(metadata) => Value::from_iter([
+                ("width", Value::from(metadata.width)),
+                ("height", Value::from(metadata.height)),
+            ]),
+            None => Value::from(()),
+        })
+    }
+
+    fn format(&self, state: &State) -> Result<Value> {
+        Ok(match self.metadata(state)? {
+            Some(metadata) => Value::from(metadata.format.as_str()),
+            None => Value::from(()),
+        })
+    }
+
+    fn exif(&self, state: &State) -> Result<Value> {
+        Ok(match self.metadata(state)? {
+            Some(metadata) => Value::from_serializable(&metadata.exif),
+            None => Value::from(()),
+        })
+    }
+
+    fn dominant_color(&self, state: &State) -> Result<Value> {
+        Ok(match self.metadata(state)? {
+            Some(metadata) => Value::from_serializable(&metadata.dominant_color),
+            None => Value::from(()),
+        })
+    }
+
     fn is_asset(&self) -> MJResult {
         let value = Value::from(!self.base.inline);
         Ok(value)    
@@ -110,7 +180,7 @@ impl Object for Resource {
         ObjectKind::Struct(self)
     }
 
-    fn call_method(&self, _: &State, name: &str, _: &[Value]) -> MJResult {
+    fn call_method(&self, state: &State, name: &str, args: &[Value]) -> MJResult {
         match name {
             "route" => self.route().map_err(Wrap::wrap),
             "cachebusted" => self.cachebusted(),
@@ -120,6 +190,14 @@ impl Object for Resource {
             "time_to_read" => self.time_to_read(),
             "is_page" => self.is_page(),
             "is_asset" => self.is_asset(),
+            "dimensions" => self.dimensions(state).map_err(Wrap::wrap),
+            "format" => self.format(state).map_err(Wrap::wrap),
+            "exif" => self.exif(state).map_err(Wrap::wrap),
+            "dominant_color" => self.dominant_color(state).map_err(Wrap::wrap),
+            "resize" => {
+                let (width, format) = from_args(args)?;
+                self.resize(state, width, format).map_err(Wrap::wrap)
+            }
             _ => Err(MJError::new(
                 MJErrorKind::UnknownMethod,
                 format!("object has no method named {name}"),
@@ -9,6 +9,8 @@ use super::*;
 use crate::db::*;
 use crate::parse::{Content, Shortcode};
 use crate::prelude::*;
+use crate::render::images::{self, Derived};
+use crate::render::minify;
 
 /// A rendering ticket, i.e. a discrete unit of rendering work that needs to be done.
 ///
@@ -18,10 +20,16 @@ use crate::prelude::*;
 #[derive(Debug)]
 pub struct Ticket {
     pub dependencies : SegQueue<(Relation, String)>,
+    /// Images derived while rendering, which are registered along with the output.
+    pub derived      : SegQueue<Derived>,
+    /// Image metadata computed while rendering, which is cached along with the output.
+    pub metadata     : SegQueue<ImageMetadata>,
     pub rev_id       : RevisionID,
     pub source       : String,
     pub ctx          : Context,
     pub page         : Page,
+    /// The page of a paginated page that this ticket renders, if any.
+    pub paginator    : Option<Paginator>,
     inner            : Value,
 }
 
@@ -51,14 +59,27 @@ impl Ticket {
 
         Self {
             dependencies: SegQueue::new(),
+            derived: SegQueue::new(),
+            metadata: SegQueue::new(),
             rev_id: rev_id.clone(),
             ctx: ctx.clone(),
             source,
             page,
+            paginator: None,
             inner,
         }
     }
 
+    /// The ID that the ticket's output (and dependencies) are stored under.
+    /// 
+    /// This is the page's ID, except for the second and later pages of a paginated page.
+    pub fn id(&self) -> &str {
+        match &self.paginator {
+            Some(paginator) if paginator.number > 1 => &paginator.id,
+            _ => &self.page.id,
+        }
+    }
+
     pub fn build(&self, env: &Environment) -> Result<String> {
         let name = match &self.page.template {
             Some(name) => name,
@@ -79,13 +100,25 @@ impl Ticket {
         let out = scope(|scope| {
             template
                 .render(context!(
-                    page => scope.object_ref(self)
+                    page => scope.object_ref(self),
+                    paginator => self.paginator.as_ref().map(|paginator| &paginator.inner)
                 ))
                 .map_err(Wrap::flatten)
         })?;
 
         self.register_dependency(Relation::PageTemplate, name)?;
 
+        let out = match self.ctx.build.minify.html {
+            true => minify::html(out),
+            false => out,
+        };
+
+        // A pagination's ID changes whenever its items do, so depending on it
+        // ensures that the first page (which shares the page's ID) is rebuilt.
+        if let Some(paginator) = &self.paginator {
+            self.register_dependency(Relation::PageAsset, &paginator.id)?;
+        }
+
         Ok(out)
     }
 
@@ -369,6 +402,9 @@ impl Ticket {
                     
                             el.set_attribute("src", &file.cachebust())
                                 .context("Failed to set img element src attribute")?;
+
+                            self.register_dependency(Relation::PageAsset, &file.id)?;
+                            self.rewrite_image(el, &file)?;
                         }
                         None => {
                             Err(eyre!("Could not cachebust image tag with src attribute {src}"))?
@@ -433,6 +469,42 @@ impl Ticket {
         Ok(buffer)
     }
 
+    /// Adds `width`/`height` attributes and a `srcset` to an image in page content, if enabled
+    /// (and not already present.)
+    fn rewrite_image(&self, el: &mut lol_html::html_content::Element, file: &InputFile) -> Result<()> {
+        let config = &self.ctx.build.images;
+
+        if !images::is_image(file) || !(config.dimensions || config.srcset) {
+            return Ok(());
+        }
+
+        let metadata = images::metadata(&self.ctx, file)?;
+        let (width, height) = (metadata.width, metadata.height);
+        self.metadata.push(metadata);
+
+        if config.dimensions && !el.has_attribute("width") && !el.has_attribute("height") {
+            el.set_attribute("width", &width.to_string())?;
+            el.set_attribute("height", &height.to_string())?;
+        }
+
+        if config.srcset && !el.has_attribute("srcset") {
+            let mut srcset = Vec::new();
+
+            for &size in config.srcset_widths.iter().filter(|&&size| size < width) {
+                let derived = images::derive(&self.ctx, file, size, config.format)?;
+
+                srcset.push(format!("{} {size}w", derived.route()));
+                self.derived.push(derived);
+            }
+
+            // The original is always the largest candidate.
+            srcset.push(format!("{} {width}w", file.cachebust()));
+            el.set_attribute("srcset", &srcset.join(", "))?;
+        }
+
+        Ok(())
+    }
+
     fn eval_shortcode(&self, state: &State, code: Shortcode) -> Result<String> {
         let name = format!("{}.html", code.name);
 
@@ -478,7 +550,7 @@ impl Ticket {
             let mut query = conn.prepare_cached("
                 SELECT child FROM dependencies
                 WHERE parent = ?1
-                AND relation = 1
+                AND relation = 0
             ")?;
 
             query
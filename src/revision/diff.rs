@@ -0,0 +1,215 @@
+//! Comparison of the inputs, routes and output of two revisions.
+
+use std::collections::BTreeMap;
+
+use console::style;
+use itertools::Itertools;
+use similar::{ChangeTag, TextDiff};
+
+use crate::db::*;
+use crate::prelude::*;
+
+/// The set of keys that were added, removed or changed between two revisions.
+#[derive(Debug, Default)]
+pub struct Delta {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl Delta {
+    /// Computes the delta between two keyed maps, where a key present in both
+    /// maps is considered changed if its values differ.
+    fn compute<V: PartialEq>(old: &BTreeMap<String, V>, new: &BTreeMap<String, V>) -> Self {
+        let mut delta = Self::default();
+
+        for (key, value) in new {
+            match old.get(key) {
+                Some(old_value) if old_value != value => delta.changed.push(key.to_owned()),
+                Some(_) => (),
+                None => delta.added.push(key.to_owned()),
+            }
+        }
+
+        delta.removed.extend(
+            old.keys()
+                .filter(|key| !new.contains_key(*key))
+                .cloned()
+        );
+
+        delta
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    fn print(&self, title: &str) {
+        println!("{}", style(title).bold());
+
+        if self.is_empty() {
+            println!("  {}", style("(no changes)").dim());
+            return;
+        }
+
+        for key in &self.added {
+            println!("  {} {key}", style("+").green().bold());
+        }
+
+        for key in &self.removed {
+            println!("  {} {key}", style("-").red().bold());
+        }
+
+        for key in &self.changed {
+            println!("  {} {key}", style("~").yellow().bold());
+        }
+    }
+}
+
+/// The full set of differences between two revisions.
+#[derive(Debug)]
+pub struct Changes {
+    /// Input files, keyed by their path.
+    pub files: Delta,
+    /// Routes, keyed by their URL.
+    pub routes: Delta,
+    /// Rendered pages, keyed by their route.
+    pub outputs: Delta,
+}
+
+impl Changes {
+    /// Computes the changes made between revisions `old` and `new`.
+    pub fn compute(ctx: &Context, old: &str, new: &str) -> Result<Self> {
+        let conn = ctx.db.get_ro()?;
+
+        Ok(Self {
+            files: Delta::compute(
+                &load_files(&conn, old)?,
+                &load_files(&conn, new)?,
+            ),
+            routes: Delta::compute(
+                &load_routes(&conn, old)?,
+                &load_routes(&conn, new)?,
+            ),
+            outputs: Delta::compute(
+                &load_outputs(&conn, old)?,
+                &load_outputs(&conn, new)?,
+            ),
+        })
+    }
+
+    pub fn print(&self) {
+        self.files.print("Files");
+        self.routes.print("Routes");
+        self.outputs.print("Rendered pages");
+    }
+}
+
+/// Prints a unified diff of the rendered output of every page that changed
+/// between revisions `old` and `new`.
+pub fn print_patch(ctx: &Context, old: &str, new: &str, routes: &[String]) -> Result<()> {
+    let conn = ctx.db.get_ro()?;
+
+    let old_outputs = load_outputs(&conn, old)?;
+    let new_outputs = load_outputs(&conn, new)?;
+
+    for route in routes {
+        let (Some(before), Some(after)) = (old_outputs.get(route), new_outputs.get(route)) else {
+            continue;
+        };
+
+        let diff = TextDiff::from_lines(before, after);
+
+        println!();
+        println!("{}", style(format!("--- {route} ({old})")).bold());
+        println!("{}", style(format!("+++ {route} ({new})")).bold());
+
+        for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+            println!("{}", style(hunk.header()).cyan());
+
+            for change in hunk.iter_changes() {
+                let line = change.to_string_lossy();
+                let line = line.trim_end_matches('\n');
+
+                match change.tag() {
+                    ChangeTag::Insert => println!("{}", style(format!("+{line}")).green()),
+                    ChangeTag::Delete => println!("{}", style(format!("-{line}")).red()),
+                    ChangeTag::Equal => println!(" {line}"),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+record! {
+    Name  => Row,
+    key   => String,
+    value => String
+}
+
+fn load_map(conn: &Connection, sql: &str, rev_id: &str) -> Result<BTreeMap<String, String>> {
+    let map = conn
+        .prepare(sql)?
+        .query_and_then([rev_id], Row::from_row)?
+        .map_ok(|row| (row.key, row.value))
+        .try_collect()?;
+
+    Ok(map)
+}
+
+/// Maps the path of every file in a revision to its ID.
+fn load_files(conn: &Connection, rev_id: &str) -> Result<BTreeMap<String, String>> {
+    load_map(conn, "
+        SELECT input_files.path AS key, input_files.id AS value FROM input_files
+        JOIN revision_files ON revision_files.id = input_files.id
+        WHERE revision_files.revision = ?1
+    ", rev_id)
+}
+
+/// Maps every route in a revision to the ID of the file it points to.
+fn load_routes(conn: &Connection, rev_id: &str) -> Result<BTreeMap<String, String>> {
+    load_map(conn, "
+        SELECT route AS key, id AS value FROM routes
+        WHERE revision = ?1
+    ", rev_id)
+}
+
+/// Maps the route of every page in a revision to its rendered output.
+fn load_outputs(conn: &Connection, rev_id: &str) -> Result<BTreeMap<String, String>> {
+    load_map(conn, "
+        SELECT routes.route AS key, output.content AS value FROM routes
+        JOIN output ON output.id = routes.id
+        AND output.revision = routes.revision
+        WHERE routes.revision = ?1
+        AND routes.kind = 2
+    ", rev_id)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn delta() {
+        let old = BTreeMap::from([
+            ("kept".to_string(), "a"),
+            ("changed".to_string(), "b"),
+            ("removed".to_string(), "c"),
+        ]);
+
+        let new = BTreeMap::from([
+            ("kept".to_string(), "a"),
+            ("changed".to_string(), "B"),
+            ("added".to_string(), "d"),
+        ]);
+
+        let delta = Delta::compute(&old, &new);
+
+        assert_eq!(delta.added, vec!["added"]);
+        assert_eq!(delta.removed, vec!["removed"]);
+        assert_eq!(delta.changed, vec!["changed"]);
+        assert!(Delta::compute(&old, &old).is_empty());
+    }
+}
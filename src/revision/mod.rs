@@ -0,0 +1,491 @@
+//! Inspection and management of site revisions.
+
+mod diff;
+
+use console::style;
+use itertools::Itertools;
+use tabled::settings::Style;
+use tabled::{Table, Tabled};
+
+pub use diff::*;
+
+use crate::db::*;
+use crate::prelude::*;
+
+/// Resolves a user-provided revision identifier into a [`Revision`].
+///
+/// The identifier is first matched against user-assigned names. If no revision
+/// has that name, it is then treated as a (potentially partial) revision ID.
+pub fn resolve(ctx: &Context, target: &str) -> Result<Revision> {
+    let conn = ctx.db.get_ro()?;
+
+    let mut by_name = conn.prepare("
+        SELECT * FROM revisions
+        WHERE name = ?1
+    ")?;
+
+    if let Some(revision) = by_name
+        .query_and_then([target], Revision::from_row)?
+        .next()
+    {
+        return Ok(revision?);
+    }
+
+    let mut by_id = conn.prepare("
+        SELECT * FROM revisions
+        WHERE substr(id, 1, length(?1)) = ?1
+    ")?;
+
+    let mut matches: Vec<Revision> = by_id
+        .query_and_then([target], Revision::from_row)?
+        .try_collect()?;
+
+    match matches.len() {
+        1 => Ok(matches.remove(0)),
+        0 => {
+            let err = eyre!("Could not find a revision with the name or ID \"{target}\".")
+                .suggestion("Use `ftl revision list` to see all known revisions.");
+
+            bail!(err)
+        }
+        n => {
+            let err = eyre!("The revision ID prefix \"{target}\" is ambiguous ({n} revisions match).")
+                .suggestion("Provide more characters of the ID, or use the revision's name instead.");
+
+            bail!(err)
+        }
+    }
+}
+
+record! {
+    Name   => ListRow,
+    id     => String,
+    name   => Option<String>,
+    time   => Option<String>,
+    pinned => bool,
+    stable => bool,
+    live   => bool,
+    pages  => i64,
+    assets => i64
+}
+
+#[derive(Tabled)]
+struct ListEntry {
+    #[tabled(rename = "ID")]
+    id: String,
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Time")]
+    time: String,
+    #[tabled(rename = "Pinned")]
+    pinned: &'static str,
+    #[tabled(rename = "Stable")]
+    stable: &'static str,
+    #[tabled(rename = "Live")]
+    live: &'static str,
+    #[tabled(rename = "Pages")]
+    pages: i64,
+    #[tabled(rename = "Assets")]
+    assets: i64,
+}
+
+impl From<ListRow> for ListEntry {
+    fn from(row: ListRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name.unwrap_or_default(),
+            time: row.time.unwrap_or_default(),
+            pinned: yes_no(row.pinned),
+            stable: yes_no(row.stable),
+            live: yes_no(row.live),
+            pages: row.pages,
+            assets: row.assets,
+        }
+    }
+}
+
+/// Prints a table of all known revisions, most recent first.
+pub fn list(ctx: &Context) -> Result<()> {
+    let conn = ctx.db.get_ro()?;
+
+    let mut query = conn.prepare("
+        SELECT revisions.*,
+            (
+                SELECT COUNT(*) FROM revision_files
+                JOIN pages ON pages.id = revision_files.id
+                WHERE revision_files.revision = revisions.id
+            ) AS pages,
+            (
+                SELECT COUNT(*) FROM revision_files
+                JOIN input_files ON input_files.id = revision_files.id
+                WHERE revision_files.revision = revisions.id
+                AND input_files.inline = FALSE
+            ) AS assets
+        FROM revisions
+        ORDER BY time IS NULL, time DESC
+    ")?;
+
+    let entries: Vec<ListEntry> = query
+        .query_and_then([], ListRow::from_row)?
+        .map_ok(ListEntry::from)
+        .try_collect()?;
+
+    if entries.is_empty() {
+        eprintln!("No revisions found - try running `ftl build` first.");
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        Table::new(entries).with(Style::rounded())
+    );
+
+    Ok(())
+}
+
+#[derive(Tabled)]
+struct FileEntry {
+    #[tabled(rename = "Path")]
+    path: String,
+    #[tabled(rename = "ID")]
+    id: String,
+}
+
+#[derive(Tabled)]
+struct RouteEntry {
+    #[tabled(rename = "Route")]
+    route: String,
+    #[tabled(rename = "Kind")]
+    kind: String,
+    #[tabled(rename = "ID")]
+    id: String,
+}
+
+record! {
+    Name => OutputRow,
+    id   => String,
+    kind => OutputKind,
+    size => i64
+}
+
+#[derive(Tabled)]
+struct OutputEntry {
+    #[tabled(rename = "ID")]
+    id: String,
+    #[tabled(rename = "Kind")]
+    kind: String,
+    #[tabled(rename = "Size")]
+    size: String,
+}
+
+/// Prints detailed information about the specified revision, including its files,
+/// routes and outputs, as well as how it differs from the revision before it.
+pub fn inspect(ctx: &Context, target: &str) -> Result<()> {
+    use indicatif::HumanBytes;
+
+    let revision = resolve(ctx, target)?;
+    let conn = ctx.db.get_ro()?;
+    let rev_id = revision.id.as_str();
+
+    println!("{} {}", style("Revision").bold(), revision.id);
+    println!("  Name:   {}", revision.name.as_deref().unwrap_or("-"));
+    println!("  Time:   {}", revision.time.as_deref().unwrap_or("-"));
+    println!("  Pinned: {}", yes_no(revision.pinned));
+    println!("  Stable: {}", yes_no(revision.stable));
+    println!("  Live:   {}", yes_no(revision.live));
+    println!();
+
+    let files: Vec<FileEntry> = conn
+        .prepare("
+            SELECT input_files.* FROM input_files
+            JOIN revision_files ON revision_files.id = input_files.id
+            WHERE revision_files.revision = ?1
+            ORDER BY input_files.path
+        ")?
+        .query_and_then([rev_id], InputFile::from_row)?
+        .map_ok(|file| FileEntry {
+            path: file.path.to_string_lossy().into_owned(),
+            id: file.id,
+        })
+        .try_collect()?;
+
+    let routes: Vec<RouteEntry> = conn
+        .prepare("
+            SELECT * FROM routes
+            WHERE revision = ?1
+            ORDER BY route
+        ")?
+        .query_and_then([rev_id], Route::from_row)?
+        .map_ok(|route| RouteEntry {
+            route: route.route,
+            kind: format!("{:?}", route.kind),
+            id: route.id,
+        })
+        .try_collect()?;
+
+    let outputs: Vec<OutputEntry> = conn
+        .prepare("
+            SELECT DISTINCT id, kind, length(content) AS size FROM output
+            WHERE revision = ?1
+            ORDER BY kind, id
+        ")?
+        .query_and_then([rev_id], OutputRow::from_row)?
+        .map_ok(|row| OutputEntry {
+            id: row.id,
+            kind: format!("{:?}", row.kind),
+            size: HumanBytes(row.size as u64).to_string(),
+        })
+        .try_collect()?;
+
+    println!("{} ({})", style("Files").bold(), files.len());
+    println!("{}\n", Table::new(files).with(Style::rounded()));
+
+    println!("{} ({})", style("Routes").bold(), routes.len());
+    println!("{}\n", Table::new(routes).with(Style::rounded()));
+
+    println!("{} ({})", style("Outputs").bold(), outputs.len());
+    println!("{}\n", Table::new(outputs).with(Style::rounded()));
+
+    match previous(ctx, &revision)? {
+        Some(prev) => {
+            println!(
+                "{} {}\n",
+                style("Changes since revision").bold(),
+                prev.id
+            );
+
+            Changes::compute(ctx, &prev.id, rev_id)?.print();
+        }
+        None => println!("{}", style("No previous stable revision to compare against.").dim())
+    }
+
+    Ok(())
+}
+
+/// Prints the changes made between revisions `old` and `new`, optionally followed by
+/// a unified diff of every page whose rendered output changed.
+pub fn diff(ctx: &Context, old: &str, new: &str, patch: bool) -> Result<()> {
+    let old = resolve(ctx, old)?;
+    let new = resolve(ctx, new)?;
+
+    println!(
+        "{} {} {} {}\n",
+        style("Changes from revision").bold(),
+        old.id,
+        style("to").bold(),
+        new.id
+    );
+
+    let changes = Changes::compute(ctx, &old.id, &new.id)?;
+    changes.print();
+
+    if patch {
+        print_patch(ctx, &old.id, &new.id, &changes.outputs.changed)?;
+    }
+
+    Ok(())
+}
+
+/// Assigns a user-provided name to the specified revision.
+pub fn name(ctx: &Context, target: &str, name: &str) -> Result<()> {
+    let revision = resolve(ctx, target)?;
+
+    let _guard = ctx.db.write_lock();
+    let conn = ctx.db.get_rw()?;
+
+    let mut taken = conn.prepare("
+        SELECT 1 FROM revisions
+        WHERE name = ?1
+        AND id != ?2
+    ")?;
+
+    if taken.exists([name, &revision.id])? {
+        let err = eyre!("The name \"{name}\" is already assigned to another revision.")
+            .suggestion("Revision names must be unique - pick a different name.");
+
+        bail!(err)
+    }
+
+    conn.execute(
+        "UPDATE revisions SET name = ?1 WHERE id = ?2",
+        [name, &revision.id]
+    )?;
+
+    eprintln!("Named revision {} \"{name}\".", revision.id);
+
+    Ok(())
+}
+
+/// Pins or unpins the specified revision.
+pub fn set_pinned(ctx: &Context, target: &str, pinned: bool) -> Result<()> {
+    let revision = resolve(ctx, target)?;
+
+    let _guard = ctx.db.write_lock();
+    let conn = ctx.db.get_rw()?;
+
+    conn.execute(
+        "UPDATE revisions SET pinned = ?1 WHERE id = ?2",
+        params![pinned, &revision.id]
+    )?;
+
+    eprintln!(
+        "{} revision {}.",
+        if pinned { "Pinned" } else { "Unpinned" },
+        revision.id
+    );
+
+    Ok(())
+}
+
+/// Makes the specified revision live.
+pub fn checkout(ctx: &Context, target: &str) -> Result<()> {
+    let revision = resolve(ctx, target)?;
+
+    if !revision.stable {
+        let err = eyre!("Cannot check out revision {}, as it is not stable.", revision.id)
+            .note("Unstable revisions never finished building, so they have no output to serve.");
+
+        bail!(err)
+    }
+
+    let _guard = ctx.db.write_lock();
+    let conn = ctx.db.get_rw()?;
+
+    conn.execute(
+        "UPDATE revisions SET live = (id = ?1)",
+        [&revision.id]
+    )?;
+
+    eprintln!("Checked out revision {}.", revision.id);
+
+    if let Some(latest) = latest_stable(ctx)? {
+        if latest.id != revision.id {
+            eprintln!(
+                "{} `ftl serve` will serve this revision until the next build.",
+                style("Note:").yellow().bold()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Determines which already-built revision `ftl serve` should start from, if any.
+///
+/// - If a revision was explicitly requested, it is resolved and returned.
+/// - Otherwise, if (outside of development mode) the live revision is not the most recent
+///   stable revision, the site has been rolled back and the live revision is returned.
+/// - Otherwise, [`None`] is returned, indicating that the site should be built as usual.
+pub fn serve_target(ctx: &Context, requested: Option<&str>) -> Result<Option<Revision>> {
+    if let Some(target) = requested {
+        let revision = resolve(ctx, target)?;
+
+        if !revision.stable {
+            let err = eyre!("Cannot serve revision {}, as it is not stable.", revision.id)
+                .note("Unstable revisions never finished building, so they have no output to serve.");
+
+            bail!(err)
+        }
+
+        return Ok(Some(revision));
+    }
+
+    if ctx.devel_mode() {
+        return Ok(None);
+    }
+
+    match (live(ctx)?, latest_stable(ctx)?) {
+        (Some(live), Some(latest)) if live.id != latest.id => {
+            eprintln!(
+                "{} revision {} is checked out - serving it instead of building.",
+                style("Note:").yellow().bold(),
+                live.id
+            );
+
+            Ok(Some(live))
+        }
+        _ => Ok(None)
+    }
+}
+
+/// Finds the live revision, if any.
+pub fn live(ctx: &Context) -> Result<Option<Revision>> {
+    let conn = ctx.db.get_ro()?;
+
+    let mut query = conn.prepare("
+        SELECT * FROM revisions
+        WHERE live = TRUE
+        LIMIT 1
+    ")?;
+
+    let live = match query
+        .query_and_then([], Revision::from_row)?
+        .next()
+    {
+        Some(live) => Ok(Some(live?)),
+        None => Ok(None)
+    };
+
+    // Necessary because lifetime bullshit
+    #[allow(clippy::let_and_return)]
+    live
+}
+
+/// Finds the most recently stabilized revision, if any.
+pub fn latest_stable(ctx: &Context) -> Result<Option<Revision>> {
+    let conn = ctx.db.get_ro()?;
+
+    let mut query = conn.prepare("
+        SELECT * FROM revisions
+        WHERE stable = TRUE
+        ORDER BY time DESC
+        LIMIT 1
+    ")?;
+
+    let latest = match query
+        .query_and_then([], Revision::from_row)?
+        .next()
+    {
+        Some(latest) => Ok(Some(latest?)),
+        None => Ok(None)
+    };
+
+    // Necessary because lifetime bullshit
+    #[allow(clippy::let_and_return)]
+    latest
+}
+
+/// Finds the most recent stable revision that was stabilized before the provided one.
+///
+/// If the provided revision is unstable, this is simply the most recent stable revision.
+fn previous(ctx: &Context, revision: &Revision) -> Result<Option<Revision>> {
+    let conn = ctx.db.get_ro()?;
+
+    let mut query = conn.prepare("
+        SELECT * FROM revisions
+        WHERE stable = TRUE
+        AND id != ?1
+        AND (?2 IS NULL OR time < ?2)
+        ORDER BY time DESC
+        LIMIT 1
+    ")?;
+
+    let previous = match query
+        .query_and_then(params![&revision.id, &revision.time], Revision::from_row)?
+        .next()
+    {
+        Some(previous) => Ok(Some(previous?)),
+        None => Ok(None)
+    };
+
+    // Necessary because lifetime bullshit
+    #[allow(clippy::let_and_return)]
+    previous
+}
+
+fn yes_no(value: bool) -> &'static str {
+    match value {
+        true => "yes",
+        false => "no",
+    }
+}
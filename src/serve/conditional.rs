@@ -0,0 +1,71 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::http::header::{IF_MODIFIED_SINCE, IF_NONE_MATCH};
+use axum::http::HeaderMap;
+
+/// Determines whether or not a conditional `GET` or `HEAD` request can be answered with `304 Not Modified`,
+/// given the current validators for the requested resource.
+///
+/// As per RFC 9110, `If-None-Match` takes precedence over `If-Modified-Since` when both are present.
+pub fn is_not_modified(headers: &HeaderMap, etag: &str, last_modified: SystemTime) -> bool {
+    if let Some(if_none_match) = headers.get(IF_NONE_MATCH) {
+        let Ok(if_none_match) = if_none_match.to_str() else {
+            return false;
+        };
+
+        // Weak comparison is used for GET and HEAD, so any W/ prefixes are ignored.
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+    }
+
+    let Some(if_modified_since) = headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok())
+    else {
+        return false;
+    };
+
+    truncate(last_modified) <= if_modified_since
+}
+
+/// Truncates a timestamp to whole seconds, the precision of HTTP dates.
+pub fn truncate(time: SystemTime) -> SystemTime {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn conditionals() {
+        let modified = UNIX_EPOCH + Duration::from_millis(1_700_000_000_500);
+        let date = httpdate::fmt_http_date(modified);
+
+        let check = |name, value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, HeaderValue::from_str(value).unwrap());
+            is_not_modified(&headers, "\"abc\"", modified)
+        };
+
+        assert!(check(IF_NONE_MATCH, "\"abc\""));
+        assert!(check(IF_NONE_MATCH, "\"xyz\", W/\"abc\""));
+        assert!(check(IF_NONE_MATCH, "*"));
+        assert!(!check(IF_NONE_MATCH, "\"xyz\""));
+
+        assert!(check(IF_MODIFIED_SINCE, &date));
+        assert!(!check(IF_MODIFIED_SINCE, "Thu, 01 Jan 1970 00:00:00 GMT"));
+        assert!(!check(IF_MODIFIED_SINCE, "garbage"));
+
+        assert!(!is_not_modified(&HeaderMap::new(), "\"abc\"", modified));
+    }
+}
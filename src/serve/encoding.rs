@@ -0,0 +1,122 @@
+use axum::http::header::ACCEPT_ENCODING;
+use axum::http::HeaderMap;
+
+/// The content codings FTL can serve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+    Identity,
+}
+
+impl Encoding {
+    /// The name of the coding, as used in `Accept-Encoding` and `Content-Encoding`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Brotli => "br",
+            Self::Gzip => "gzip",
+            Self::Identity => "identity",
+        }
+    }
+
+    /// Derives the entity tag for this coding of a resource from the tag of its identity coding.
+    ///
+    /// Strong entity tags must differ between codings, as the bytes on the wire differ.
+    pub fn tag(self, etag: &str) -> String {
+        match self {
+            Self::Identity => etag.to_owned(),
+            _ => format!("{}-{}\"", etag.trim_end_matches('"'), self.name()),
+        }
+    }
+}
+
+/// Picks the best coding the client accepts out of those available.
+///
+/// Codings are ranked by their quality values, with ties broken in favor of Brotli, then gzip.
+/// Identity is used if no compressed coding is acceptable.
+pub fn negotiate(headers: &HeaderMap, available: &[Encoding]) -> Encoding {
+    let Some(accept) = headers
+        .get(ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return Encoding::Identity;
+    };
+
+    let mut wildcard = None;
+    let mut preferences = Vec::new();
+
+    for item in accept.split(',') {
+        let mut parts = item.split(';').map(str::trim);
+
+        let Some(coding) = parts.next().filter(|coding| !coding.is_empty()) else {
+            continue;
+        };
+
+        let quality = parts
+            .find_map(|param| param.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        match coding.to_ascii_lowercase().as_str() {
+            "*" => wildcard = Some(quality),
+            "br" => preferences.push((Encoding::Brotli, quality)),
+            "gzip" | "x-gzip" => preferences.push((Encoding::Gzip, quality)),
+            _ => (),
+        }
+    }
+
+    let quality = |encoding: Encoding| {
+        preferences
+            .iter()
+            .find(|(candidate, _)| *candidate == encoding)
+            .map(|(_, quality)| *quality)
+            .or(wildcard)
+            .unwrap_or(0.0)
+    };
+
+    [Encoding::Brotli, Encoding::Gzip]
+        .into_iter()
+        .filter(|encoding| available.contains(encoding))
+        .map(|encoding| (encoding, quality(encoding)))
+        .filter(|(_, quality)| *quality > 0.0)
+        // Reverse so that max_by picks the earliest (i.e. preferred) coding on ties.
+        .rev()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(encoding, _)| encoding)
+        .unwrap_or(Encoding::Identity)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn negotiation() {
+        use Encoding::*;
+
+        let all = [Brotli, Gzip];
+
+        let check = |accept: &str, available: &[Encoding]| {
+            let mut headers = HeaderMap::new();
+            headers.insert(ACCEPT_ENCODING, HeaderValue::from_str(accept).unwrap());
+            negotiate(&headers, available)
+        };
+
+        assert_eq!(negotiate(&HeaderMap::new(), &all), Identity);
+        assert_eq!(check("gzip, deflate, br", &all), Brotli);
+        assert_eq!(check("gzip, deflate, br", &[Gzip]), Gzip);
+        assert_eq!(check("gzip, deflate, br", &[]), Identity);
+        assert_eq!(check("br;q=0.5, gzip", &all), Gzip);
+        assert_eq!(check("br;q=0, *", &all), Gzip);
+        assert_eq!(check("*", &all), Brotli);
+        assert_eq!(check("deflate", &all), Identity);
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(Encoding::Identity.tag("\"abc\""), "\"abc\"");
+        assert_eq!(Encoding::Brotli.tag("\"abc\""), "\"abc-br\"");
+        assert_eq!(Encoding::Gzip.tag("\"abc\""), "\"abc-gzip\"");
+    }
+}
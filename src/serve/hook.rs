@@ -0,0 +1,237 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+use minijinja::value::*;
+use minijinja::{Error as MJError, ErrorKind as MJErrorKind, State};
+
+/// The `response` object exposed to hook templates, through which they can set
+/// the status code, headers and cookies of their response - or redirect elsewhere.
+///
+/// Clones share the same underlying properties, so one can be handed to the template
+/// while another is kept to read the results back.
+#[derive(Debug, Clone, Default)]
+pub struct HookResponse(Arc<Mutex<Parts>>);
+
+/// The response properties set by a hook template.
+#[derive(Debug, Clone, Default)]
+pub struct Parts {
+    pub status: StatusCode,
+    pub headers: Vec<(String, String)>,
+}
+
+impl HookResponse {
+    /// Yields the response properties set by the template.
+    pub fn into_parts(self) -> Parts {
+        self.parts().clone()
+    }
+
+    fn parts(&self) -> MutexGuard<'_, Parts> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn set_status(&self, code: u16) -> Result<Value, MJError> {
+        self.parts().status = status(code)?;
+        Ok(Value::from(""))
+    }
+
+    fn set_header(&self, name: &str, value: &str) -> Result<Value, MJError> {
+        validate_header(name, value)?;
+
+        let mut parts = self.parts();
+        parts.headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+        parts.headers.push((name.to_owned(), value.to_owned()));
+
+        Ok(Value::from(""))
+    }
+
+    fn set_cookie(&self, name: &str, value: &str, kwargs: Kwargs) -> Result<Value, MJError> {
+        validate_cookie_name(name)?;
+
+        let mut cookie = format!("{name}={}", urlencoding::encode(value));
+
+        let path = kwargs.get::<Option<&str>>("path")?.unwrap_or("/");
+        validate_cookie_attribute("path", path)?;
+        cookie.push_str(&format!("; Path={path}"));
+
+        if let Some(max_age) = kwargs.get::<Option<i64>>("max_age")? {
+            cookie.push_str(&format!("; Max-Age={max_age}"));
+        }
+
+        if let Some(domain) = kwargs.get::<Option<&str>>("domain")? {
+            validate_cookie_attribute("domain", domain)?;
+            cookie.push_str(&format!("; Domain={domain}"));
+        }
+
+        if let Some(same_site) = kwargs.get::<Option<&str>>("same_site")? {
+            validate_cookie_attribute("same_site", same_site)?;
+            cookie.push_str(&format!("; SameSite={same_site}"));
+        }
+
+        if kwargs.get::<Option<bool>>("http_only")?.unwrap_or(true) {
+            cookie.push_str("; HttpOnly");
+        }
+
+        if kwargs.get::<Option<bool>>("secure")?.unwrap_or(false) {
+            cookie.push_str("; Secure");
+        }
+
+        kwargs.assert_all_used()?;
+        validate_header("Set-Cookie", &cookie)?;
+
+        // Unlike other headers, multiple cookies can be set at once.
+        self.parts().headers.push(("Set-Cookie".to_owned(), cookie));
+
+        Ok(Value::from(""))
+    }
+
+    fn delete_cookie(&self, name: &str) -> Result<Value, MJError> {
+        validate_cookie_name(name)?;
+
+        let cookie = format!("{name}=; Path=/; Max-Age=0");
+        validate_header("Set-Cookie", &cookie)?;
+
+        self.parts().headers.push(("Set-Cookie".to_owned(), cookie));
+
+        Ok(Value::from(""))
+    }
+
+    fn redirect(&self, location: &str, code: Option<u16>) -> Result<Value, MJError> {
+        let code = status(code.unwrap_or(302))?;
+
+        if !code.is_redirection() {
+            return Err(MJError::new(
+                MJErrorKind::InvalidOperation,
+                format!("{code} is not a redirect status code"),
+            ));
+        }
+
+        self.parts().status = code;
+        self.set_header("Location", location)
+    }
+}
+
+impl std::fmt::Display for HookResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<Hook Response Object>")
+    }
+}
+
+impl Object for HookResponse {
+    fn call_method(&self, _: &State, name: &str, args: &[Value]) -> Result<Value, MJError> {
+        match name {
+            "set_status" => {
+                let (code,) = from_args(args)?;
+                self.set_status(code)
+            }
+            "set_header" => {
+                let (name, value) = from_args(args)?;
+                self.set_header(name, value)
+            }
+            "set_cookie" => {
+                let (name, value, kwargs) = from_args(args)?;
+                self.set_cookie(name, value, kwargs)
+            }
+            "delete_cookie" => {
+                let (name,) = from_args(args)?;
+                self.delete_cookie(name)
+            }
+            "redirect" => {
+                let (location, code) = from_args(args)?;
+                self.redirect(location, code)
+            }
+            _ => Err(MJError::new(
+                MJErrorKind::UnknownMethod,
+                format!("object has no method named {name}"),
+            )),
+        }
+    }
+}
+
+fn status(code: u16) -> Result<StatusCode, MJError> {
+    StatusCode::from_u16(code).map_err(|_| MJError::new(
+        MJErrorKind::InvalidOperation,
+        format!("{code} is not a valid status code"),
+    ))
+}
+
+fn validate_header(name: &str, value: &str) -> Result<(), MJError> {
+    let valid = HeaderName::from_bytes(name.as_bytes()).is_ok()
+        && HeaderValue::from_str(value).is_ok();
+
+    match valid {
+        true => Ok(()),
+        false => Err(MJError::new(
+            MJErrorKind::InvalidOperation,
+            format!("invalid response header ({name}: {value})"),
+        )),
+    }
+}
+
+/// Cookie names can't contain separators (or be empty), as they would corrupt the `Set-Cookie` header.
+fn validate_cookie_name(name: &str) -> Result<(), MJError> {
+    let valid = !name.is_empty() && name.chars().all(|c| {
+        c.is_ascii_graphic() && !"()<>@,;:\\\"/[]?={}".contains(c)
+    });
+
+    match valid {
+        true => Ok(()),
+        false => Err(MJError::new(
+            MJErrorKind::InvalidOperation,
+            format!("invalid cookie name ({name:?})"),
+        )),
+    }
+}
+
+/// Cookie attributes can't contain semicolons, which would let them inject attributes of their own.
+fn validate_cookie_attribute(kwarg: &str, value: &str) -> Result<(), MJError> {
+    match value.contains(';') {
+        false => Ok(()),
+        true => Err(MJError::new(
+            MJErrorKind::InvalidOperation,
+            format!("invalid cookie {kwarg} ({value:?})"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use minijinja::{context, Environment};
+
+    #[test]
+    fn response_object() {
+        let env = Environment::new();
+        let response = HookResponse::default();
+
+        let output = env.render_str(
+            "{{ response.set_status(201) }}\
+             {{ response.set_header('X-Test', 'a') }}\
+             {{ response.set_header('x-test', 'b') }}\
+             {{ response.set_cookie('session', 'a b', max_age=60) }}ok",
+            context! { response => Value::from_object(response.clone()) }
+        ).unwrap();
+
+        assert_eq!(output, "ok");
+
+        let parts = response.into_parts();
+        assert_eq!(parts.status, StatusCode::CREATED);
+        assert_eq!(parts.headers, [
+            ("x-test".to_owned(), "b".to_owned()),
+            ("Set-Cookie".to_owned(), "session=a%20b; Path=/; Max-Age=60; HttpOnly".to_owned()),
+        ]);
+
+        let response = HookResponse::default();
+        assert!(response.delete_cookie("bad name").is_err());
+        assert!(response.delete_cookie("a\nb").is_err());
+        assert!(response.set_cookie("a;b", "c", Kwargs::from_iter(None::<(&str, Value)>)).is_err());
+        assert!(response.set_cookie("a", "b", Kwargs::from_iter([("path", Value::from("/; Secure"))])).is_err());
+        response.delete_cookie("session").unwrap();
+
+        let response = HookResponse::default();
+        assert!(response.redirect("/elsewhere", Some(200)).is_err());
+        response.redirect("/elsewhere", None).unwrap();
+        assert_eq!(response.into_parts().status, StatusCode::FOUND);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::time::Duration;
+
+use itertools::Itertools;
+use moka::future::Cache;
+
+use crate::db::*;
+use crate::prelude::*;
+
+use super::request::Request;
+use super::resource::Resource;
+
+/// The response caches of every hook in a revision that has caching enabled, keyed by hook ID.
+/// 
+/// Each hook gets its own cache (with its own TTL and size limit), which is carried over to the next revision
+/// if neither the hook nor any of the templates it depends on have changed.
+#[derive(Default)]
+pub struct HookCaches {
+    caches: HashMap<String, HookCache>,
+}
+
+#[derive(Clone)]
+struct HookCache {
+    /// Hash of the hook's ID and the IDs of its (transitive) template dependencies.
+    fingerprint: u64,
+    ignore_queries: Vec<String>,
+    vary: Vec<String>,
+    cache: Cache<String, Resource>,
+}
+
+record! {
+    Name       => Dependency,
+    id         => String,
+    dependency => Option<String>
+}
+
+impl HookCaches {
+    /// Creates the hook caches for a revision, reusing those from the previous revision where possible.
+    pub fn load(ctx: &Context, rev_id: &RevisionID, previous: &Self) -> Result<Self> {
+        let conn = ctx.db.get_ro()?;
+
+        let mut get_hooks = conn.prepare("
+            SELECT hooks.* FROM hooks
+            JOIN revision_files ON revision_files.id = hooks.id
+            WHERE revision_files.revision = ?1
+            AND hooks.cache = TRUE
+        ")?;
+
+        // Template dependencies are only computed for the most recently loaded revision,
+        // which is always the one being loaded here.
+        let mut get_dependencies = conn.prepare("
+            SELECT hooks.id, dependencies.child AS dependency FROM hooks
+            LEFT JOIN dependencies
+                ON dependencies.parent = hooks.template
+                AND dependencies.relation = 0
+            WHERE hooks.id = ?1
+            ORDER BY dependency
+        ")?;
+
+        let mut caches = HashMap::new();
+
+        for hook in get_hooks.query_and_then([rev_id.as_ref()], Hook::from_row)? {
+            let hook = hook?;
+
+            let mut hasher = seahash::SeaHasher::new();
+            hasher.write(hook.id.as_bytes());
+
+            for dependency in get_dependencies.query_and_then([&hook.id], Dependency::from_row)? {
+                if let Some(dependency) = dependency?.dependency {
+                    hasher.write(dependency.as_bytes());
+                }
+            }
+
+            let fingerprint = hasher.finish();
+
+            let cache = match previous.caches.get(&hook.id) {
+                Some(cache) if cache.fingerprint == fingerprint => {
+                    debug!("Keeping response cache for hook {}.", hook.id);
+                    cache.clone()
+                }
+                _ => HookCache::new(ctx, &hook, fingerprint)
+            };
+
+            caches.insert(hook.id, cache);
+        }
+
+        Ok(Self { caches })
+    }
+
+    /// Computes the cache key for a request to the specified hook, if its response can be cached.
+    pub fn key(&self, hook: &str, request: &Request) -> Option<String> {
+        let cache = self.caches.get(hook)?;
+
+        if !request.is_safe() {
+            return None;
+        }
+
+        let mut query = form_urlencoded::Serializer::new(String::new());
+
+        for (name, value) in request.queries() {
+            if !cache.ignore_queries.contains(&name) {
+                query.append_pair(&name, &value);
+            }
+        }
+
+        let mut key = format!("{}?{}", request.uri.path(), query.finish());
+
+        for name in &cache.vary {
+            let values = request.headers
+                .get_all(name.as_str())
+                .iter()
+                .map(|value| String::from_utf8_lossy(value.as_bytes()))
+                .join(", ");
+
+            key.push_str(&format!("\n{name}: {values}"));
+        }
+
+        Some(key)
+    }
+
+    /// The value of the `Vary` header for responses from the specified hook, if its cache varies on any request headers.
+    pub fn vary(&self, hook: &str) -> Option<String> {
+        let cache = self.caches.get(hook)?;
+
+        match cache.vary.is_empty() {
+            true => None,
+            false => Some(cache.vary.join(", ")),
+        }
+    }
+
+    pub fn get(&self, hook: &str, key: &str) -> Option<Resource> {
+        self.caches.get(hook)?.cache.get(key)
+    }
+
+    pub fn insert(&self, hook: &str, key: String, resource: Resource) {
+        if let Some(cache) = self.caches.get(hook) {
+            debug!("Caching output of hook {hook} for key {key:?}");
+            cache.cache.blocking().insert(key, resource);
+        }
+    }
+}
+
+impl HookCache {
+    fn new(ctx: &Context, hook: &Hook, fingerprint: u64) -> Self {
+        let mut cache = Cache::builder();
+
+        cache = match hook.cache_max_entries {
+            Some(entries) => cache.max_capacity(entries as u64),
+            None => cache
+                .max_capacity(ctx.serve.cache_max_size * 1024 * 1024)
+                .weigher(|_, value: &Resource| value.size() as u32)
+        };
+
+        cache = match hook.cache_ttl {
+            Some(ttl) => cache.time_to_live(Duration::from_secs(ttl as u64)),
+            None => cache
+                .time_to_idle(Duration::from_secs(ctx.serve.cache_tti))
+                .time_to_live(Duration::from_secs(ctx.serve.cache_ttl))
+        };
+
+        let split = |list: &str| -> Vec<String> {
+            list
+                .split('\n')
+                .filter(|item| !item.is_empty())
+                .map(ToOwned::to_owned)
+                .collect()
+        };
+
+        Self {
+            fingerprint,
+            ignore_queries: split(&hook.cache_ignore_queries),
+            vary: split(&hook.cache_vary),
+            cache: cache.build(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn keys() {
+        let cache = HookCache {
+            fingerprint: 0,
+            ignore_queries: vec!["utm_source".to_owned()],
+            vary: vec!["accept-language".to_owned()],
+            cache: Cache::new(16),
+        };
+
+        let caches = HookCaches {
+            caches: HashMap::from([("hook".to_owned(), cache)]),
+        };
+
+        let mut request = Request::get("/search?q=rust&utm_source=feed&a=1".parse().unwrap());
+        request.headers.insert("Accept-Language", HeaderValue::from_static("en-US"));
+
+        let key = caches.key("hook", &request).unwrap();
+        assert_eq!(key, "/search?a=1&q=rust\naccept-language: en-US");
+
+        request.uri = "/search?a=1&q=rust".parse().unwrap();
+        assert_eq!(caches.key("hook", &request).unwrap(), key);
+
+        request.method = axum::http::Method::POST;
+        assert!(caches.key("hook", &request).is_none());
+        assert!(caches.key("other", &Request::get("/".parse().unwrap())).is_none());
+
+        assert_eq!(caches.vary("hook").as_deref(), Some("accept-language"));
+        assert_eq!(caches.vary("other"), None);
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use mime_guess::mime;
+
+/// Determines the `Content-Type` of a file.
+///
+/// In order of precedence, this considers:
+/// - User-provided overrides from the `[serve.mime]` table in `ftl.toml`, keyed by extension.
+/// - The file's extension.
+/// - The file's contents (i.e. "magic number" sniffing.) Valid UTF-8 that can't be identified any
+///   further is assumed to be plain text.
+pub fn content_type(overrides: &HashMap<String, String>, extension: Option<&str>, bytes: &[u8]) -> String {
+    let extension = extension.map(str::to_ascii_lowercase);
+
+    if let Some(mime) = extension.as_ref().and_then(|ext| overrides.get(ext)) {
+        return mime.to_owned();
+    }
+
+    if let Some(mime) = extension.and_then(|ext| mime_guess::from_ext(&ext).first()) {
+        return with_charset(mime);
+    }
+
+    if let Some(kind) = infer::get(bytes) {
+        return kind.mime_type().to_owned();
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(_) => with_charset(mime::TEXT_PLAIN),
+        Err(_) => mime::APPLICATION_OCTET_STREAM.to_string(),
+    }
+}
+
+/// Appends a UTF-8 charset parameter to textual MIME types.
+fn with_charset(mime: mime::Mime) -> String {
+    let textual = mime.type_() == mime::TEXT
+        || matches!(mime.subtype(), mime::JSON | mime::JAVASCRIPT | mime::XML)
+        || mime.suffix() == Some(mime::JSON)
+        || mime.suffix() == Some(mime::XML);
+
+    match textual && mime.get_param(mime::CHARSET).is_none() {
+        true => format!("{mime}; charset=utf-8"),
+        false => mime.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn content_types() {
+        let overrides = HashMap::from([
+            ("wasm".to_string(), "application/wasm".to_string()),
+        ]);
+
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+        assert_eq!(content_type(&overrides, Some("wasm"), b""), "application/wasm");
+        assert_eq!(content_type(&overrides, Some("PNG"), b""), "image/png");
+        assert_eq!(content_type(&overrides, Some("json"), b"{}"), "application/json; charset=utf-8");
+        assert_eq!(content_type(&overrides, Some("svg"), b""), "image/svg+xml; charset=utf-8");
+        assert_eq!(content_type(&overrides, None, png), "image/png");
+        assert_eq!(content_type(&overrides, None, b"hello"), "text/plain; charset=utf-8");
+        assert_eq!(content_type(&overrides, None, b"\xff\xfe\xfd"), "application/octet-stream");
+    }
+}
@@ -1,16 +1,28 @@
+mod conditional;
+mod encoding;
+mod hook;
+mod hook_cache;
+mod mime;
+mod patterns;
+mod preheat;
+mod range;
+mod redirect;
+mod request;
 mod resource;
 
 use std::net::SocketAddr;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::sync::Arc;
 
-use arc_swap::ArcSwap as Swap;
+use arc_swap::{ArcSwap as Swap, ArcSwapOption as SwapOption};
 
 use axum::extract::State;
-use axum::http::{StatusCode, Uri};
+use axum::http::header::{CONTENT_RANGE, ETAG, LAST_MODIFIED, VARY};
+use axum::body::Bytes;
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode, Uri};
 use axum::response::{IntoResponse, Response};
 use axum::response::sse::*;
-use axum::routing::get;
+use axum::routing::{any, get};
 use axum::Router;
 
 use futures_util::stream::{Stream, StreamExt};
@@ -23,6 +35,11 @@ use crate::prelude::*;
 use crate::render::Renderer;
 use crate::watch::init_watcher;
 
+use self::hook_cache::HookCaches;
+use self::patterns::HookPatterns;
+use self::range::RangeRequest;
+pub use self::redirect::Redirects;
+use self::request::Request;
 use self::resource::*;
 
 type Server = Arc<InnerServer>;
@@ -31,15 +48,33 @@ type SseEvent = Result<Event, std::convert::Infallible>;
 pub struct InnerServer {
     pub renderer: Swap<Renderer>,
     pub rev_id: Swap<String>,
+    /// When the current revision was built - used as the `Last-Modified` time for static resources.
+    pub modified: Swap<SystemTime>,
+    /// User-defined redirects for the current revision.
+    pub redirects: Swap<Redirects>,
+    /// Hook routes with path parameters or wildcards for the current revision.
+    pub hook_patterns: Swap<HookPatterns>,
     pub cache: Cache<Uri, Resource>,
+    /// Response caches for hooks, which are kept separate from the main cache
+    /// as they have their own settings and can outlive a revision.
+    pub hook_caches: Swap<HookCaches>,
     pub notif: Notify,
+    /// The report from the most recent failed rebuild, if the site is currently failing to build.
+    /// Pushed to development mode clients over the live reload channel.
+    pub build_error: SwapOption<String>,
     pub ctx: Context,
+    /// Whether or not to watch the site source and migrate to new revisions as they are built.
+    pub watch: bool,
 }
 
 impl InnerServer {
-    pub fn new(ctx: &Context, renderer: Renderer) -> Server {
+    pub fn new(ctx: &Context, renderer: Renderer, watch: bool) -> Result<Server> {
         let renderer = Arc::new(renderer);
         let rev_id = renderer.rev_id.clone();
+        let modified = revision_time(ctx, &rev_id);
+        let redirects = Redirects::load(ctx, &rev_id)?;
+        let hook_patterns = HookPatterns::load(ctx, &rev_id)?;
+        let hook_caches = HookCaches::load(ctx, &rev_id, &HookCaches::default())?;
 
         let cache = Cache::builder()
             .max_capacity(ctx.serve.cache_max_size * 1024 * 1024)
@@ -53,13 +88,19 @@ impl InnerServer {
             })
             .build();
             
-        Arc::new(Self {
+        Ok(Arc::new(Self {
             renderer: Swap::new(renderer),
             rev_id: Swap::new(rev_id.into_inner()),
+            modified: Swap::from_pointee(modified),
+            redirects: Swap::from_pointee(redirects),
+            hook_patterns: Swap::from_pointee(hook_patterns),
             cache,
+            hook_caches: Swap::from_pointee(hook_caches),
             notif: Notify::new(),
+            build_error: SwapOption::empty(),
             ctx: ctx.clone(),
-        })
+            watch,
+        }))
     }
 
     /// Bootstraps the Tokio runtime and starts the internal `async` site serving code.
@@ -74,23 +115,32 @@ impl InnerServer {
     }
 
     async fn _serve(self: &Server) -> Result<()> {
-        let server = self.clone();
+        if self.watch {
+            let server = self.clone();
 
-        tokio::task::spawn(async move {
-            let (_debouncer, mut rx) = init_watcher(&server.ctx)
-                .expect("Failed to create watcher");
+            tokio::task::spawn(async move {
+                let (_debouncer, mut rx) = init_watcher(&server.ctx)
+                    .expect("Failed to create watcher");
 
-            while let Ok(id) = rx.recv().await {
-                server.migrate_revision(id);
-                server.notif.notify_waiters();
-            }
+                while let Ok(id) = rx.recv().await {
+                    server.migrate_revision(id);
+                    server.notif.notify_waiters();
+                }
 
-            error!("Watch receiver closed - this shouldn't happen!");
-        });
+                error!("Watch receiver closed - this shouldn't happen!");
+            });
+        } else {
+            info!("Serving revision {} as-is; the site source will not be watched.", self.rev_id.load());
+        }
+
+        let server = self.clone();
+        if let Err(err) = tokio::task::spawn_blocking(move || server.preheat()).await {
+            warn!("Failed to preheat cache - {err:?}");
+        }
 
         let mut app = Router::new()
-            .route("/", get(fetch_resource))
-            .route("/*path", get(fetch_resource));
+            .route("/", any(fetch_resource))
+            .route("/*path", any(fetch_resource));
 
         if self.ctx.devel_mode() {
             app = app.route("/ftl_livereload", get(live_reload));
@@ -153,43 +203,104 @@ impl InnerServer {
         }
     }
 
-    fn migrate_revision(&self, rev_id: RevisionID) {
+    fn migrate_revision(self: &Server, rev_id: RevisionID) {
         info!("Migrating to revision {rev_id}...");
 
-        let renderer = Renderer::new(
-            &self.ctx,
-            Some(&rev_id)
-        );
+        let migration = Renderer::new(&self.ctx, Some(&rev_id)).and_then(|renderer| {
+            let redirects = Redirects::load(&self.ctx, &rev_id)?;
+            let hook_patterns = HookPatterns::load(&self.ctx, &rev_id)?;
+            let hook_caches = HookCaches::load(&self.ctx, &rev_id, &self.hook_caches.load())?;
+            Ok((renderer, redirects, hook_patterns, hook_caches))
+        });
 
-        match renderer {
-            Ok(renderer) => {
+        match migration {
+            Ok((renderer, redirects, hook_patterns, hook_caches)) => {
                 self.renderer.swap(renderer.into());
+                self.redirects.store(redirects.into());
+                self.hook_patterns.store(hook_patterns.into());
+                self.hook_caches.store(hook_caches.into());
+                self.modified.store(revision_time(&self.ctx, &rev_id).into());
                 self.rev_id.swap(rev_id.into_inner());
+                self.build_error.store(None);
                 self.cache.invalidate_all();
         
                 info!("Successfully migrated to revision {rev_id}.");
+
+                // Preheating does blocking database reads, and shouldn't hold up live reloading.
+                let server = self.clone();
+                tokio::task::spawn_blocking(move || server.preheat());
             }
             Err(err) => {
-                error!("Failed to migrate revision - {err:?}")
+                error!("Failed to migrate revision - {err:?}");
+
+                let report = ansi_to_html::convert_escaped(&format!("{err:?}"))
+                    .unwrap_or_else(|_| err.to_string());
+
+                self.build_error.store(Some(report.into()));
             }
         }
     }
 }
 
-async fn fetch_resource(State(server): State<Server>, uri: Uri) -> Response {
-    debug!("GET request for URI {uri:?}");
+/// Looks up when the specified revision was built, falling back to the current time
+/// if it can't be determined.
+fn revision_time(ctx: &Context, rev_id: &RevisionID) -> SystemTime {
+    let query = || -> Result<u64> {
+        let conn = ctx.db.get_ro()?;
+
+        // Revision times are stored in local time.
+        let secs = conn.query_row(
+            "SELECT CAST(strftime('%s', time, 'utc') AS INTEGER) FROM revisions WHERE id = ?1",
+            [rev_id.as_ref()],
+            |row| row.get(0)
+        )?;
+
+        Ok(secs)
+    };
+
+    match query() {
+        Ok(secs) => UNIX_EPOCH + Duration::from_secs(secs),
+        Err(err) => {
+            warn!("Could not determine build time for revision {rev_id} - {err:?}");
+            SystemTime::now()
+        }
+    }
+}
 
-    if let Some(cached) = server.cache.get(&uri) {
-        debug!("Serving URI {uri:?} from cache.");
-        return cached.into_response();
+async fn fetch_resource(
+    State(server): State<Server>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes
+) -> Response {
+    debug!("{method} request for URI {uri:?}");
+
+    let request = Request {
+        method,
+        uri,
+        headers,
+        body,
+    };
+
+    // Only GET and HEAD responses are cached; anything else has to go through to the hook.
+    if request.is_safe() {
+        if let Some(cached) = server.cache.get(&request.uri) {
+            debug!("Serving URI {:?} from cache.", request.uri);
+            return respond(&server, &request.headers, cached);
+        }
     }
 
-    let resource = Resource::from_uri(
+    let safe = request.is_safe();
+    let uri = request.uri.clone();
+    let headers = request.headers.clone();
+
+    let resource = Resource::from_request(
         &server,
-        uri.clone()
+        request
     ).await;
 
-    if resource.should_cache() {
+    if safe && resource.should_cache() {
         debug!("Caching {uri:?}");
         server.cache.insert(
             uri, 
@@ -197,7 +308,62 @@ async fn fetch_resource(State(server): State<Server>, uri: Uri) -> Response {
         ).await;
     }
 
-    resource.into_response()
+    respond(&server, &headers, resource)
+}
+
+/// Converts a resource into a response, attaching validators to static resources
+/// and answering conditional requests with `304 Not Modified` where possible.
+fn respond(server: &Server, headers: &HeaderMap, resource: Resource) -> Response {
+    let Some(etag) = resource.etag() else {
+        return resource.into_response();
+    };
+
+    let encodings = resource.encodings();
+    let encoding = encoding::negotiate(headers, &encodings);
+    let etag = encoding.tag(&etag);
+
+    let modified = conditional::truncate(**server.modified.load());
+    let not_modified = conditional::is_not_modified(headers, &etag, modified);
+
+    let len = resource.len();
+
+    // Range requests only apply to assets, and are moot if the client's copy is fresh.
+    let range = match (not_modified, len) {
+        (false, Some(len)) => range::evaluate(headers, len, &etag, modified),
+        _ => RangeRequest::Full,
+    };
+
+    let mut response = match range {
+        RangeRequest::Full => resource.into_encoded_response(encoding),
+        RangeRequest::Partial(range) => resource.into_partial_response(range),
+        RangeRequest::Unsatisfiable => (
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            [(CONTENT_RANGE, format!("bytes */{}", len.unwrap_or_default()))],
+        ).into_response(),
+    };
+
+    if not_modified {
+        *response.status_mut() = StatusCode::NOT_MODIFIED;
+        *response.body_mut() = axum::body::boxed(axum::body::Empty::new());
+    }
+
+    let validators = response.headers_mut();
+
+    // Caches need to know that the response differs by coding, even if
+    // this particular client got the identity coding.
+    if !encodings.is_empty() {
+        validators.insert(VARY, HeaderValue::from_static("accept-encoding"));
+    }
+
+    if let Ok(etag) = HeaderValue::from_str(&etag) {
+        validators.insert(ETAG, etag);
+    }
+
+    if let Ok(modified) = HeaderValue::from_str(&httpdate::fmt_http_date(modified)) {
+        validators.insert(LAST_MODIFIED, modified);
+    }
+
+    response
 }
 
 async fn live_reload(State(server): State<Server>) -> Sse<impl Stream<Item = SseEvent>> {
@@ -205,10 +371,19 @@ async fn live_reload(State(server): State<Server>) -> Sse<impl Stream<Item = Sse
     use tokio_stream::wrappers::*;
 
     let (tx, rx) = mpsc::unbounded_channel();
+    let notifier = server.clone();
+
+    // Clients that connect while the site is failing to build
+    // should see the error straight away.
+    if server.build_error.load().is_some() {
+        let _ = tx.send(());
+    }
 
     // Cursed hack to wrap the notify, because apparently
     // there's no way to "just repeat a future as a stream."
     tokio::task::spawn(async move {
+        let server = notifier;
+
         while !tx.is_closed() {
             server.notif.notified().await;
             debug!("Dispatching live reload notification...");
@@ -217,7 +392,12 @@ async fn live_reload(State(server): State<Server>) -> Sse<impl Stream<Item = Sse
     });
     
     let stream = UnboundedReceiverStream::new(rx)
-        .map(|_| Event::default().data("Live reload!"))
+        .map(move |_| match server.build_error.load_full() {
+            Some(report) => Event::default()
+                .event("build_error")
+                .data(report.as_str()),
+            None => Event::default().data("Live reload!")
+        })
         .map(Ok);
 
     Sse::new(stream)
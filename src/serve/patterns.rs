@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+
+use crate::db::*;
+use crate::prelude::*;
+
+/// The hook routes of a revision that have path parameters or wildcards, parsed once and sorted
+/// most specific first.
+#[derive(Debug, Default)]
+pub struct HookPatterns {
+    patterns: Vec<(RoutePattern, Route)>,
+}
+
+impl HookPatterns {
+    pub fn load(ctx: &Context, rev_id: &RevisionID) -> Result<Self> {
+        let conn = ctx.db.get_ro()?;
+
+        let mut query = conn.prepare("
+            SELECT * FROM routes
+            WHERE revision = ?1
+            AND kind = 1
+        ")?;
+
+        let mut patterns: Vec<_> = query
+            .query_and_then([rev_id.as_ref()], Route::from_row)?
+            .filter_ok(|route| RoutePattern::is_pattern(&route.route))
+            .filter_map_ok(|route| match RoutePattern::parse(&route.route) {
+                Ok(pattern) => Some((pattern, route)),
+                // Patterns are validated when hooks are prepared, so this should only happen
+                // if the revision was built by an older version of FTL.
+                Err(err) => {
+                    warn!("Hook route {} will not be served, as its path is invalid - {err:?}", route.route);
+                    None
+                }
+            })
+            .try_collect()?;
+
+        patterns.sort_by_cached_key(|(pattern, route)| (pattern.specificity(), route.route.to_owned()));
+
+        Ok(Self { patterns })
+    }
+
+    /// Finds the most specific hook route matching the provided path, along with its parameters.
+    pub fn find(&self, path: &str) -> Option<(Route, BTreeMap<String, String>)> {
+        self.patterns
+            .iter()
+            .find_map(|(pattern, route)| {
+                pattern
+                    .matches(path)
+                    .map(|params| (route.clone(), params))
+            })
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use axum::http::Uri;
+
+use crate::db::*;
+use crate::prelude::*;
+
+use super::request::Request;
+use super::resource::Resource;
+use super::{InnerServer, Server};
+
+impl InnerServer {
+    /// Resolves every non-hook route in the current revision and inserts it into the cache,
+    /// in the order given by `cache_preheat_priority`.
+    /// 
+    /// Routes that would push the cache past `cache_max_size` are skipped rather than inserted,
+    /// so that higher-priority entries are never evicted in favor of lower-priority ones.
+    pub fn preheat(self: &Server) {
+        if !self.ctx.serve.cache_preheat {
+            return;
+        }
+
+        if let Err(err) = self._preheat() {
+            warn!("Failed to preheat cache - {err:?}");
+        }
+    }
+
+    fn _preheat(self: &Server) -> Result<()> {
+        let rev_id = self.rev_id.load_full();
+        let priority = self.ctx.serve.cache_preheat_priority();
+        let capacity = self.ctx.serve.cache_max_size * 1024 * 1024;
+
+        info!("Preheating cache for revision {rev_id}...");
+
+        let conn = self.ctx.db.get_ro()?;
+
+        let mut query = conn.prepare("
+            SELECT * FROM routes
+            WHERE revision = ?1
+            AND kind != 1
+            ORDER BY route
+        ")?;
+
+        let mut routes: Vec<(usize, Route)> = query
+            .query_and_then([rev_id.as_str()], Route::from_row)?
+            .filter_map(|route| match route {
+                Ok(route) => priority
+                    .iter()
+                    .position(|class| class.kinds().contains(&route.kind))
+                    .map(|rank| Ok((rank, route))),
+                Err(err) => Some(Err(err))
+            })
+            .collect::<Result<_, _>>()?;
+
+        // Stable, so routes of the same class remain in lexical order.
+        routes.sort_by_key(|(rank, _)| *rank);
+
+        let mut size = 0;
+        let mut inserted = 0;
+        let mut skipped = 0;
+
+        for (_, route) in routes {
+            // Anything resolved after a migration would belong to the new revision, which preheats itself.
+            if !Arc::ptr_eq(&rev_id, &self.rev_id.load()) {
+                info!("Stopped preheating revision {rev_id}, as it is no longer current.");
+                return Ok(());
+            }
+
+            let uri: Uri = match route.route.parse() {
+                Ok(uri) => uri,
+                Err(err) => {
+                    debug!("Not preheating route {} - {err}", route.route);
+                    continue;
+                }
+            };
+
+            let resource = match Resource::from_request_sync(self, &Request::get(uri.clone())) {
+                Ok(resource) => resource,
+                Err(err) => {
+                    warn!("Not preheating route {} - {err:?}", route.route);
+                    continue;
+                }
+            };
+
+            if !resource.should_cache() || matches!(resource, Resource::Error(..)) {
+                continue;
+            }
+
+            let weight = resource.size() as u64;
+
+            if size + weight > capacity {
+                skipped += 1;
+                continue;
+            }
+
+            size += weight;
+            inserted += 1;
+
+            self.cache.blocking().insert(uri, resource);
+        }
+
+        info!("Preheated {inserted} route(s) ({} KB), skipping {skipped} that would exceed the size limit.", size / 1024);
+
+        Ok(())
+    }
+}
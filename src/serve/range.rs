@@ -0,0 +1,122 @@
+use std::ops::Range;
+use std::time::SystemTime;
+
+use axum::http::header::{IF_RANGE, RANGE};
+use axum::http::HeaderMap;
+
+/// How a request for a resource that supports byte ranges should be answered.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RangeRequest {
+    /// Send the full resource.
+    Full,
+    /// Send only the specified (half-open) range of bytes.
+    Partial(Range<u64>),
+    /// The requested range lies outside the resource.
+    Unsatisfiable,
+}
+
+/// Evaluates the `Range` and `If-Range` headers of a request against a resource of length `len`
+/// with the provided validators.
+///
+/// Only single ranges are supported - requests for multiple ranges are answered with the full resource,
+/// which RFC 9110 permits.
+pub fn evaluate(headers: &HeaderMap, len: u64, etag: &str, last_modified: SystemTime) -> RangeRequest {
+    let Some(range) = headers
+        .get(RANGE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return RangeRequest::Full;
+    };
+
+    // If-Range makes the range conditional on the resource being unchanged.
+    if let Some(if_range) = headers.get(IF_RANGE) {
+        let Ok(if_range) = if_range.to_str() else {
+            return RangeRequest::Full;
+        };
+
+        let fresh = match if_range.starts_with('"') {
+            true => if_range == etag,
+            false => httpdate::parse_http_date(if_range)
+                .map(|date| date == last_modified)
+                .unwrap_or(false),
+        };
+
+        if !fresh {
+            return RangeRequest::Full;
+        }
+    }
+
+    parse(range, len)
+}
+
+fn parse(range: &str, len: u64) -> RangeRequest {
+    let Some(spec) = range.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Full;
+    };
+
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RangeRequest::Full;
+    };
+
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // bytes=a-b
+        (Ok(start), Ok(end)) if start <= end => start..end.saturating_add(1).min(len),
+        // bytes=a-
+        (Ok(start), Err(_)) if end.is_empty() => start..len,
+        // bytes=-n (the final n bytes)
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 {
+                return RangeRequest::Unsatisfiable;
+            }
+
+            len.saturating_sub(suffix)..len
+        }
+        _ => return RangeRequest::Full,
+    };
+
+    match range.start < len {
+        true => RangeRequest::Partial(range),
+        false => RangeRequest::Unsatisfiable,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ranges() {
+        use RangeRequest::*;
+
+        assert_eq!(parse("bytes=0-99", 1000), Partial(0..100));
+        assert_eq!(parse("bytes=900-", 1000), Partial(900..1000));
+        assert_eq!(parse("bytes=-100", 1000), Partial(900..1000));
+        assert_eq!(parse("bytes=-5000", 1000), Partial(0..1000));
+        assert_eq!(parse("bytes=500-5000", 1000), Partial(500..1000));
+        assert_eq!(parse("bytes=1000-", 1000), Unsatisfiable);
+        assert_eq!(parse("bytes=-0", 1000), Unsatisfiable);
+        assert_eq!(parse("bytes=0-1,5-6", 1000), Full);
+        assert_eq!(parse("bytes=9-1", 1000), Full);
+        assert_eq!(parse("lines=0-1", 1000), Full);
+    }
+
+    #[test]
+    fn if_range() {
+        use axum::http::HeaderValue;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(RANGE, HeaderValue::from_static("bytes=0-9"));
+        headers.insert(IF_RANGE, HeaderValue::from_static("\"abc\""));
+
+        let now = SystemTime::UNIX_EPOCH;
+
+        assert_eq!(evaluate(&headers, 100, "\"abc\"", now), RangeRequest::Partial(0..10));
+        assert_eq!(evaluate(&headers, 100, "\"xyz\"", now), RangeRequest::Full);
+    }
+}
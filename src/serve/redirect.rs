@@ -0,0 +1,159 @@
+use axum::http::StatusCode;
+
+use crate::prelude::*;
+
+/// The set of user-defined redirects in effect for a revision.
+#[derive(Debug, Default)]
+pub struct Redirects {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    from: String,
+    wildcard: bool,
+    to: String,
+    status: StatusCode,
+}
+
+impl Redirects {
+    /// Loads the redirects declared in `ftl.toml`, followed by those in the revision's `redirects.toml` (if any.)
+    pub fn load(ctx: &Context, rev_id: &RevisionID) -> Result<Self> {
+        let conn = ctx.db.get_ro()?;
+
+        let mut query = conn.prepare("
+            SELECT input_files.contents FROM input_files
+            JOIN revision_files ON revision_files.id = input_files.id
+            WHERE revision_files.revision = ?1
+            AND input_files.path = ?2
+        ")?;
+
+        let file = match query
+            .query_and_then([rev_id.as_ref(), REDIRECTS_FILENAME], |row| row.get::<_, Option<String>>(0))?
+            .next()
+        {
+            Some(contents) => contents?.unwrap_or_default(),
+            None => String::new(),
+        };
+
+        let file: RedirectsFile = toml::from_str(&file)
+            .wrap_err_with(|| format!("Failed to parse {REDIRECTS_FILENAME}."))?;
+
+        Self::new(ctx.redirects.iter().chain(&file.redirects))
+    }
+
+    fn new<'a>(redirects: impl IntoIterator<Item = &'a Redirect>) -> Result<Self> {
+        let rules = redirects
+            .into_iter()
+            .map(|redirect| {
+                let status = StatusCode::from_u16(redirect.status)
+                    .ok()
+                    .filter(StatusCode::is_redirection)
+                    .filter(|status| *status != StatusCode::NOT_MODIFIED)
+                    .ok_or_else(|| {
+                        eyre!("Redirect from {} has an invalid status code ({}).", redirect.from, redirect.status)
+                            .suggestion("Use one of 301, 302, 303, 307 or 308.")
+                    })?;
+
+                let (from, wildcard) = match redirect.from.strip_suffix('*') {
+                    Some(prefix) => (prefix.to_owned(), true),
+                    None => (trim_path(&redirect.from).to_owned(), false),
+                };
+
+                Ok(Rule {
+                    from,
+                    wildcard,
+                    to: redirect.to.to_owned(),
+                    status,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { rules })
+    }
+
+    /// Returns the source and target of every redirect without a wildcard, in order of precedence.
+    pub fn exact(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.rules
+            .iter()
+            .filter(|rule| !rule.wildcard)
+            .map(|rule| (rule.from.as_str(), rule.to.as_str()))
+    }
+
+    /// Returns the source of every redirect with a wildcard.
+    pub fn wildcards(&self) -> impl Iterator<Item = &str> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.wildcard)
+            .map(|rule| rule.from.as_str())
+    }
+
+    /// Finds the first redirect matching the provided path, returning its status code and target.
+    pub fn find(&self, path: &str) -> Option<(StatusCode, String)> {
+        self.rules.iter().find_map(|rule| {
+            let splat = match rule.wildcard {
+                true => path.strip_prefix(&rule.from)?,
+                false if trim_path(path) == rule.from => "",
+                false => return None,
+            };
+
+            Some((rule.status, rule.to.replace('*', splat)))
+        })
+    }
+}
+
+/// Trims trailing slashes from a path, leaving the root path as-is.
+fn trim_path(path: &str) -> &str {
+    match path {
+        "/" => "/",
+        path => path.trim_end_matches('/'),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn redirect(from: &str, to: &str, status: u16) -> Redirect {
+        Redirect {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            status,
+        }
+    }
+
+    #[test]
+    fn matching() {
+        let redirects = [
+            redirect("/old-post/", "/new-post", 301),
+            redirect("/blog/*", "/posts/*", 308),
+            redirect("/docs/*", "https://docs.example.com/", 302),
+        ];
+
+        let redirects = Redirects::new(&redirects).unwrap();
+
+        assert_eq!(
+            redirects.find("/old-post"),
+            Some((StatusCode::MOVED_PERMANENTLY, "/new-post".to_owned()))
+        );
+        assert_eq!(
+            redirects.find("/blog/2023/hello"),
+            Some((StatusCode::PERMANENT_REDIRECT, "/posts/2023/hello".to_owned()))
+        );
+        assert_eq!(
+            redirects.find("/docs/anything"),
+            Some((StatusCode::FOUND, "https://docs.example.com/".to_owned()))
+        );
+        assert_eq!(redirects.find("/old-post/nested"), None);
+        assert_eq!(redirects.find("/"), None);
+
+        assert_eq!(redirects.exact().collect::<Vec<_>>(), [("/old-post", "/new-post")]);
+        assert_eq!(redirects.wildcards().collect::<Vec<_>>(), ["/blog/", "/docs/"]);
+    }
+
+    #[test]
+    fn invalid_status() {
+        assert!(Redirects::new(&[redirect("/a", "/b", 200)]).is_err());
+        assert!(Redirects::new(&[redirect("/a", "/b", 304)]).is_err());
+    }
+}
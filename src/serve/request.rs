@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+
+use axum::body::Bytes;
+use axum::http::header::{CONTENT_TYPE, COOKIE};
+use axum::http::{HeaderMap, Method, Uri};
+
+/// The parts of an incoming request that FTL needs to resolve a resource.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: Method,
+    pub uri: Uri,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl Request {
+    /// Creates a bare `GET` request for the specified URI, with no headers or body.
+    pub fn get(uri: Uri) -> Self {
+        Self {
+            method: Method::GET,
+            uri,
+            headers: HeaderMap::new(),
+            body: Bytes::new(),
+        }
+    }
+
+    /// Whether or not the request is a `GET` or `HEAD` - the only methods allowed for static resources,
+    /// and the only methods whose responses can be cached.
+    pub fn is_safe(&self) -> bool {
+        matches!(self.method, Method::GET | Method::HEAD)
+    }
+
+    /// Decodes the query string of the request.
+    pub fn queries(&self) -> BTreeMap<String, String> {
+        parse_urlencoded(self.uri.query().unwrap_or("").as_bytes())
+    }
+
+    /// Collects the request headers, keyed by their lowercase names.
+    /// 
+    /// Repeated headers are combined into a single comma-separated value.
+    pub fn headers(&self) -> BTreeMap<String, String> {
+        let mut headers = BTreeMap::<String, String>::new();
+
+        for (name, value) in &self.headers {
+            let value = String::from_utf8_lossy(value.as_bytes());
+
+            headers
+                .entry(name.as_str().to_owned())
+                .and_modify(|existing| {
+                    existing.push_str(", ");
+                    existing.push_str(&value);
+                })
+                .or_insert_with(|| value.into_owned());
+        }
+
+        headers
+    }
+
+    /// Parses the cookies sent with the request.
+    pub fn cookies(&self) -> BTreeMap<String, String> {
+        self.headers
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(parse_cookies)
+            .collect()
+    }
+
+    /// Decodes the request body if it is a URL-encoded form.
+    pub fn form(&self) -> Option<BTreeMap<String, String>> {
+        self.content_type()?
+            .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+            .then(|| parse_urlencoded(&self.body))
+    }
+
+    /// Parses the request body if it is JSON.
+    pub fn json(&self) -> Option<serde_json::Result<serde_json::Value>> {
+        self.content_type()?
+            .eq_ignore_ascii_case("application/json")
+            .then(|| serde_json::from_slice(&self.body))
+    }
+
+    /// The request body, decoded as (lossy) UTF-8.
+    pub fn body(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// The media type of the request body, sans any parameters.
+    ///
+    /// Media types are case-insensitive, so this should be compared with `eq_ignore_ascii_case`.
+    fn content_type(&self) -> Option<&str> {
+        self.headers
+            .get(CONTENT_TYPE)?
+            .to_str()
+            .ok()?
+            .split(';')
+            .next()
+            .map(str::trim)
+    }
+}
+
+fn parse_urlencoded(input: &[u8]) -> BTreeMap<String, String> {
+    form_urlencoded::parse(input)
+        .into_owned()
+        .collect()
+}
+
+fn parse_cookies(header: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    header
+        .split(';')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| {
+            let value = value.trim().trim_matches('"');
+
+            let value = urlencoding::decode(value)
+                .map(|value| value.into_owned())
+                .unwrap_or_else(|_| value.to_owned());
+
+            (name.trim().to_owned(), value)
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn parsing() {
+        let mut request = Request::get("/hook?name=J%C3%B6rg+Smith&empty=&flag".parse().unwrap());
+
+        request.headers.append(COOKIE, HeaderValue::from_static("session=abc123; theme=\"dark\""));
+        request.headers.append(COOKIE, HeaderValue::from_static("greeting=hello%20there"));
+        request.headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/x-www-form-urlencoded; charset=utf-8"));
+        request.body = Bytes::from_static(b"email=a%40b.com&message=Hi+there");
+
+        let queries = request.queries();
+        assert_eq!(queries["name"], "Jörg Smith");
+        assert_eq!(queries["empty"], "");
+        assert_eq!(queries["flag"], "");
+
+        let cookies = request.cookies();
+        assert_eq!(cookies["session"], "abc123");
+        assert_eq!(cookies["theme"], "dark");
+        assert_eq!(cookies["greeting"], "hello there");
+
+        let form = request.form().unwrap();
+        assert_eq!(form["email"], "a@b.com");
+        assert_eq!(form["message"], "Hi there");
+
+        assert!(request.json().is_none());
+        assert_eq!(request.headers()["cookie"], "session=abc123; theme=\"dark\", greeting=hello%20there");
+
+        // Media types are case-insensitive.
+        request.headers.insert(CONTENT_TYPE, HeaderValue::from_static("Application/JSON"));
+        request.body = Bytes::from_static(b"{\"name\": \"Jane\"}");
+
+        assert_eq!(request.json().unwrap().unwrap()["name"], "Jane");
+        assert!(request.form().is_none());
+    }
+}
@@ -1,8 +1,9 @@
-use std::string::FromUtf8Error;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use arc_swap::access::Access;
-use axum::body::Bytes;
+use axum::body::{BoxBody, Bytes};
 use axum::http::{StatusCode, Uri, HeaderName, HeaderValue, HeaderMap};
 use axum::response::{IntoResponse, Response};
 use itertools::Itertools;
@@ -12,27 +13,56 @@ use crate::db::*;
 use crate::prelude::*;
 
 use super::Server;
+use super::encoding::Encoding;
+use super::hook::HookResponse;
+use super::request::Request;
+
+/// Parameters captured from the path of a request by a hook path pattern.
+type Params = std::collections::BTreeMap<String, String>;
+
+/// How many bytes of a streamed asset are read to sniff its MIME type.
+const SNIFF_LEN: usize = 8192;
 
 #[derive(Debug, Clone)]
 pub enum Resource {
-    Text(String, RouteKind),
-    Octets(Bytes),
+    Text {
+        content: String,
+        /// Precompressed variants of the content - empty if unavailable.
+        gzip: Bytes,
+        brotli: Bytes,
+        kind: RouteKind,
+    },
+    Octets {
+        id: String,
+        bytes: Bytes,
+        mime: String,
+    },
+    /// A large asset, streamed from the cache on demand rather than held in memory.
+    Stream {
+        id: String,
+        path: PathBuf,
+        len: u64,
+        mime: String,
+    },
     Hook {
         code: StatusCode,
         headers: Arc<[(String, String)]>,
         output: String,
-        cache: bool,
+    },
+    Redirect {
+        code: StatusCode,
+        location: String,
     },
     Error(String, StatusCode)
 }
 
 impl Resource {
-    pub async fn from_uri(server: &Server, uri: Uri) -> Self {
+    pub async fn from_request(server: &Server, request: Request) -> Self {
         let server_copy = server.clone();
-        let uri_copy = uri.clone();
+        let uri = request.uri.clone();
 
         let handle = tokio::task::spawn_blocking(move || {
-            Self::from_uri_sync(&server_copy, uri_copy)
+            Self::from_request_sync(&server_copy, &request)
         });
 
         handle
@@ -57,36 +87,69 @@ impl Resource {
             })
     }
 
-    fn from_uri_sync(server: &Server, uri: Uri) -> Result<Self> {
-        let Some(route) = Self::lookup_route(server, &uri)? else {
-            let error_page = server.render_error_page(
-                StatusCode::NOT_FOUND,
-                &uri,
-                None
-            )?;
-
-            return Ok(Self::Error(
-                error_page,
-                StatusCode::NOT_FOUND
-            ))
+    pub fn from_request_sync(server: &Server, request: &Request) -> Result<Self> {
+        let uri = &request.uri;
+
+        // User-defined redirects take precedence over routes.
+        if let Some((code, location)) = server.redirects.load().find(uri.path()) {
+            return Ok(Self::Redirect {
+                code,
+                location: with_query(location, uri)
+            })
+        }
+
+        let Some((route, params)) = Self::lookup_route(server, uri)? else {
+            return Self::from_error(server, uri, StatusCode::NOT_FOUND)
         };
 
+        // Only hooks can respond to methods other than GET and HEAD.
+        if route.kind != RouteKind::Hook && !request.is_safe() {
+            return Self::from_error(server, uri, StatusCode::METHOD_NOT_ALLOWED)
+        }
+
         match route.kind {
-            RouteKind::Asset | RouteKind::RedirectAsset => Self::from_asset(&route),
-            RouteKind::Page | RouteKind::RedirectPage | RouteKind::Stylesheet => Self::from_text(server, &route),
-            RouteKind::Hook => Self::from_hook(server, &uri, &route),
+            RouteKind::Asset | RouteKind::RedirectAsset => Self::from_asset(server, &route),
+            RouteKind::RedirectPage => Self::from_alias(server, uri, &route),
+            RouteKind::RedirectSearchIndex => Self::from_search_index_alias(server, &route),
+            RouteKind::Page
+            | RouteKind::Stylesheet
+            | RouteKind::SearchIndex
+            | RouteKind::Feed
+            | RouteKind::Sitemap
+            | RouteKind::Robots => Self::from_text(server, &route),
+            RouteKind::Hook => Self::from_hook(server, request, &route, params),
         }
     }
 
+    fn from_error(server: &Server, uri: &Uri, code: StatusCode) -> Result<Self> {
+        let error_page = server.render_error_page(
+            code,
+            uri,
+            None
+        )?;
+
+        Ok(Self::Error(error_page, code))
+    }
+
+    /// Looks up the route matching a URI, along with any parameters captured from the path.
+    /// 
+    /// Exact routes are checked first, preferring those that match the query string as well as the path,
+    /// then in the order static files (assets and stylesheets), pages (and their aliases), then hooks. Failing that, the URI is matched against any hook path patterns, most specific first.
     #[inline]
-    fn lookup_route(server: &Server, uri: &Uri) -> Result<Option<Route>> {
+    fn lookup_route(server: &Server, uri: &Uri) -> Result<Option<(Route, Params)>> {
         let conn = server.ctx.db.get_ro()?;
         let rev_id = server.rev_id.load();
     
-        let mut query = conn.prepare("
+        let mut query = conn.prepare_cached("
             SELECT * FROM routes
             WHERE route IN (?1, ?2)
             AND revision = ?3
+            ORDER BY route = ?2 DESC, CASE kind
+                WHEN 2 THEN 1
+                WHEN 4 THEN 1
+                WHEN 1 THEN 2
+                ELSE 0
+            END
         ")?;
         
         // We trim any leading slashes, just in case the user accidentally adds one.
@@ -111,29 +174,122 @@ impl Resource {
             rev_id.as_ref()
         ];
     
-        let route = match query
+        if let Some(route) = query
             .query_and_then(parameters, Route::from_row)?
             .next() 
         {
-            Some(route) => Ok(Some(route?)),
-            None => Ok(None)
-        };
+            return Ok(Some((route?, Params::new())))
+        }
 
-        // Necessary because lifetime bullshit
-        #[allow(clippy::let_and_return)]
-        route
+        Ok(server.hook_patterns.load().find(path))
     }
 
     #[inline]
-    fn from_asset(route: &Route) -> Result<Self> {
-        let path = format!(
-            "{SITE_CACHE_PATH}{}",
-            route.id
+    fn from_asset(server: &Server, route: &Route) -> Result<Self> {
+        let conn = server.ctx.db.get_ro()?;
+
+        let file = conn.query_row(
+            "SELECT * FROM input_files WHERE id = ?1",
+            [&route.id],
+            InputFile::from_row
+        )?;
+
+        // Inline files (such as JSON) live in the database rather than the cache.
+        let bytes = match file.inline {
+            true => Bytes::from(file.contents.unwrap_or_default()),
+            false => {
+                let path = PathBuf::from(format!(
+                    "{SITE_CACHE_PATH}{}",
+                    route.id
+                ));
+
+                let len = std::fs::metadata(&path)?.len();
+
+                if len > server.ctx.serve.stream_threshold() {
+                    return Self::from_large_asset(server, route, &file, path, len);
+                }
+
+                std::fs::read(path).map(Bytes::from)?
+            }
+        };
+
+        let mime = super::mime::content_type(
+            &server.ctx.serve.mime,
+            file.extension.as_deref(),
+            &bytes
+        );
+
+        Ok(Self::Octets {
+            id: route.id.to_owned(),
+            bytes,
+            mime
+        })
+    }
+
+    fn from_large_asset(server: &Server, route: &Route, file: &InputFile, path: PathBuf, len: u64) -> Result<Self> {
+        use std::io::Read;
+
+        // Sniffing only needs the first few bytes of the file.
+        let mut head = Vec::with_capacity(SNIFF_LEN);
+
+        std::fs::File::open(&path)?
+            .take(SNIFF_LEN as u64)
+            .read_to_end(&mut head)?;
+
+        let mime = super::mime::content_type(
+            &server.ctx.serve.mime,
+            file.extension.as_deref(),
+            &head
         );
 
-        let bytes = std::fs::read(path).map(Bytes::from)?;
+        Ok(Self::Stream {
+            id: route.id.to_owned(),
+            path,
+            len,
+            mime
+        })
+    }
+
+    /// Redirects a page alias to the canonical route of the page.
+    #[inline]
+    fn from_alias(server: &Server, uri: &Uri, route: &Route) -> Result<Self> {
+        let conn = server.ctx.db.get_ro()?;
 
-        Ok(Self::Octets(bytes))
+        let canonical = conn.query_row(
+            "SELECT route FROM routes
+            WHERE id = ?1
+            AND revision = ?2
+            AND kind = 2",
+            [&*route.id, server.rev_id.load().as_ref()],
+            |row| row.get::<_, String>(0)
+        )?;
+
+        Ok(Self::Redirect {
+            code: StatusCode::MOVED_PERMANENTLY,
+            location: with_query(canonical, uri)
+        })
+    }
+
+    /// Redirects the stable search index route to the current (cachebusted) index.
+    ///
+    /// The index changes with every revision, so the redirect is temporary.
+    #[inline]
+    fn from_search_index_alias(server: &Server, route: &Route) -> Result<Self> {
+        let conn = server.ctx.db.get_ro()?;
+
+        let location = conn.query_row(
+            "SELECT route FROM routes
+            WHERE id = ?1
+            AND revision = ?2
+            AND kind = 6",
+            [&*route.id, server.rev_id.load().as_ref()],
+            |row| row.get::<_, String>(0)
+        )?;
+
+        Ok(Self::Redirect {
+            code: StatusCode::TEMPORARY_REDIRECT,
+            location
+        })
     }
 
     #[inline]
@@ -154,15 +310,25 @@ impl Resource {
             Some(output) => {
                 let output: Output = output?;
                 let mut content = output.content;
+                let mut gzip = Bytes::from(output.gzip);
+                let mut brotli = Bytes::from(output.brotli);
 
                 // If we're in development mode, append the live reload script to the HTML.
-                if server.ctx.devel_mode() {
+                // The precompressed variants don't include it, so they have to go.
+                if server.ctx.devel_mode() && route.kind == RouteKind::Page {
                     content += include_str!("live_reload.html");
+                    gzip.clear();
+                    brotli.clear();
                 }
 
-                Ok(Self::Text(content, route.kind))
+                Ok(Self::Text {
+                    content,
+                    gzip,
+                    brotli,
+                    kind: route.kind
+                })
             }
-            None => panic!("Could not find output for page with ID {id}!")
+            None => bail!("Could not find output for route {} (ID {id}).", route.route)
         };
 
         // Necessary because lifetime bullshit
@@ -171,8 +337,9 @@ impl Resource {
     }
 
     #[inline]
-    fn from_hook(server: &Server, uri: &Uri, route: &Route) -> Result<Self> {
+    fn from_hook(server: &Server, request: &Request, route: &Route, params: Params) -> Result<Self> {
         let conn = server.ctx.db.get_ro()?;
+        let uri = &request.uri;
         let id = &*route.id;
 
         let mut query = conn.prepare("
@@ -184,15 +351,7 @@ impl Resource {
             .query_and_then([id], Hook::from_row)? 
             .next()
         else {
-            let error_page = server.render_error_page(
-                StatusCode::NOT_FOUND,
-                uri,
-                None
-            )?;
-
-            return Ok(
-                Self::Error(error_page, StatusCode::NOT_FOUND)
-            )
+            return Self::from_error(server, uri, StatusCode::NOT_FOUND)
         };
 
         let hook: Hook = hook?;
@@ -208,60 +367,77 @@ impl Resource {
                 )
             })?;
 
-        // Scuffed HTTP query parsing.
-        // I don't think there are any edge cases lurking uncovered in here, but
-        // we'll see.
-        let queries = uri
-            .query()
-            .unwrap_or("")
-            .split('&')
-            .filter_map(|kwarg| {
-                let mut split = kwarg.split('=').take(2);
-
-                match (split.next(), split.next()) {
-                    (Some(k), Some(v)) => Some((k, v)),
-                    _ => None
-                }
-            })
-            .map(|(k, v)| {
-                use std::borrow::Cow;
-                use urlencoding::decode;
-                
-                let lossy = |err: FromUtf8Error| {
-                    String::from_utf8_lossy(
-                        err.as_bytes()
-                    ).into_owned()
-                };
+        let json = match request.json() {
+            Some(Ok(json)) => Value::from_serializable(&json),
+            Some(Err(err)) => {
+                debug!("Rejecting malformed JSON body for hook {uri:?} - {err}");
+                return Self::from_error(server, uri, StatusCode::BAD_REQUEST)
+            }
+            None => Value::from(())
+        };
+
+        let caches = server.hook_caches.load();
+        let key = caches.key(&hook.id, request);
+
+        if let Some(cached) = key.as_ref().and_then(|key| caches.get(&hook.id, key)) {
+            debug!("Serving output of hook {} from cache.", hook.id);
+            return Ok(cached);
+        }
+
+        let response = HookResponse::default();
 
-                (
-                    decode(k)
-                        .map(Cow::into_owned)
-                        .unwrap_or_else(lossy),
-                    decode(v)
-                        .map(Cow::into_owned)
-                        .unwrap_or_else(lossy)
-                )
-            });
-        
         let output = template.render(context! {
             path => uri.path(),
-            queries => Value::from_iter(queries)
+            method => request.method.as_str(),
+            params,
+            queries => request.queries(),
+            headers => request.headers(),
+            cookies => request.cookies(),
+            form => request.form().unwrap_or_default(),
+            json,
+            body => request.body(),
+            response => Value::from_object(response.clone()),
         })
         .context("Hook rendering failed")
         .suggestion("Check your template code for bugs.")?;
 
-        let headers = hook.headers
+        let parts = response.into_parts();
+
+        // Headers set by the template override those declared for the hook,
+        // except for cookies, which are additive.
+        let mut headers: Vec<(String, String)> = hook.headers
             .split('\n')
             .map(ToOwned::to_owned)
             .tuples()
+            .filter(|(name, _): &(String, String)| {
+                !parts.headers
+                    .iter()
+                    .any(|(set, _)| set.eq_ignore_ascii_case(name))
+            })
+            .chain(parts.headers.iter().cloned())
             .collect();
 
-        Ok(Self::Hook {
-            code: StatusCode::OK,
-            headers,
+        // Downstream caches need to know which request headers the response was keyed on.
+        if let Some(vary) = caches.vary(&hook.id) {
+            headers.push(("Vary".to_owned(), vary));
+        }
+
+        let sets_cookies = headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("set-cookie"));
+
+        let resource = Self::Hook {
+            code: parts.status,
+            headers: headers.into(),
             output,
-            cache: hook.cache
-        })
+        };
+
+        // Responses that set cookies are specific to the request.
+        if let Some(key) = key.filter(|_| !sets_cookies) {
+            caches.insert(&hook.id, key, resource.clone());
+        }
+
+        Ok(resource)
     }
 }
 
@@ -270,19 +446,126 @@ impl Resource {
         use Resource::*;
 
         match self {
-            Hook { cache, .. } => *cache,
+            // Hooks have their own caches.
+            Hook { .. } | Stream { .. } => false,
             _ => true
         }
     }
     
+    /// Computes a strong entity tag for the resource, if it is static.
+    /// 
+    /// Assets are content-addressed, so their ID suffices. Rendered output is hashed instead,
+    /// as its ID is shared between revisions.
+    pub fn etag(&self) -> Option<String> {
+        use Resource::*;
+
+        match self {
+            Text { content, .. } => Some(format!("\"{:016x}\"", seahash::hash(content.as_bytes()))),
+            Octets { id, .. } | Stream { id, .. } => Some(format!("\"{id}\"")),
+            _ => None
+        }
+    }
+
+    /// The length (in bytes) of the resource, if it supports range requests.
+    pub fn len(&self) -> Option<u64> {
+        use Resource::*;
+
+        match self {
+            Octets { bytes, .. } => Some(bytes.len() as u64),
+            Stream { len, .. } => Some(*len),
+            _ => None
+        }
+    }
+
+    /// Converts the resource into a `206 Partial Content` response containing only the specified range.
+    /// 
+    /// Only resources that support range requests (see [`Resource::len`]) can be converted this way.
+    pub fn into_partial_response(self, range: Range<u64>) -> Response {
+        use Resource::*;
+
+        let (len, mime, body) = match self {
+            Octets { bytes, mime, .. } => {
+                let len = bytes.len() as u64;
+                let body = bytes.slice(range.start as usize..range.end as usize);
+
+                (len, mime, axum::body::boxed(axum::body::Full::new(body)))
+            }
+            Stream { path, len, mime, .. } => match stream_file(&path, range.clone()) {
+                Ok(body) => (len, mime, body),
+                Err(err) => {
+                    error!("Failed to stream {path:?} - {err:?}");
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            },
+            _ => unreachable!("Only assets support range requests.")
+        };
+
+        (
+            StatusCode::PARTIAL_CONTENT,
+            [
+                ("Content-Type", mime),
+                ("Content-Range", format!("bytes {}-{}/{len}", range.start, range.end - 1)),
+                ("Content-Length", (range.end - range.start).to_string()),
+                ("Accept-Ranges", "bytes".to_owned()),
+                ("Cache-Control", "max-age=31536000, immutable".to_owned()),
+            ],
+            body
+        ).into_response()
+    }
+
+    /// The compressed codings available for the resource.
+    pub fn encodings(&self) -> Vec<Encoding> {
+        let Self::Text { gzip, brotli, .. } = self else {
+            return Vec::new();
+        };
+
+        let mut encodings = Vec::new();
+
+        if !brotli.is_empty() {
+            encodings.push(Encoding::Brotli);
+        }
+
+        if !gzip.is_empty() {
+            encodings.push(Encoding::Gzip);
+        }
+
+        encodings
+    }
+
+    /// Converts the resource into a response, using the provided coding if it is available.
+    pub fn into_encoded_response(self, encoding: Encoding) -> Response {
+        use axum::http::header::CONTENT_ENCODING;
+
+        let body = match (&self, encoding) {
+            (Self::Text { brotli, .. }, Encoding::Brotli) if !brotli.is_empty() => brotli.clone(),
+            (Self::Text { gzip, .. }, Encoding::Gzip) if !gzip.is_empty() => gzip.clone(),
+            _ => return self.into_response()
+        };
+
+        let mut response = self.into_response();
+
+        *response.body_mut() = axum::body::boxed(axum::body::Full::new(body));
+        response.headers_mut().insert(
+            CONTENT_ENCODING,
+            HeaderValue::from_static(encoding.name())
+        );
+
+        response
+    }
+
     pub fn size(&self) -> usize {
         use std::mem::size_of_val;
         use Resource::*;
 
         match self {
-            Text(content, kind) => content.len() + size_of_val(kind),
-            Octets(bytes) => bytes.len(),
-            Hook { code, headers, output, cache } => {
+            Text { content, gzip, brotli, kind } => {
+                content.len() + gzip.len() + brotli.len() + size_of_val(kind)
+            },
+            Octets { id, bytes, mime } => id.len() + bytes.len() + mime.len(),
+            Stream { id, path, len, mime } => {
+                id.len() + path.as_os_str().len() + size_of_val(len) + mime.len()
+            },
+            Hook { code, headers, output } => {
                 let headers: usize = headers
                     .iter()
                     .map(|(name, value)| {
@@ -290,8 +573,9 @@ impl Resource {
                     })
                     .sum();
 
-                size_of_val(code) + headers + output.len() + size_of_val(cache)
+                size_of_val(code) + headers + output.len()
             },
+            Redirect { code, location } => size_of_val(code) + location.len(),
             Error(content, code) => content.len() + size_of_val(code)
         }
     }
@@ -302,7 +586,7 @@ impl IntoResponse for Resource {
         use Resource::*;
 
         match self {
-            Text(content, kind) => {
+            Text { content, kind, .. } => {
                 let headers = match kind {
                     RouteKind::Page | RouteKind::RedirectPage => [
                         ("Content-Type", "text/html; charset=utf-8"),
@@ -312,6 +596,27 @@ impl IntoResponse for Resource {
                         ("Content-Type", "text/css; charset=utf-8"),
                         ("Cache-Control", "max-age=31536000, immutable"),
                     ],
+                    RouteKind::SearchIndex => [
+                        ("Content-Type", "application/json"),
+                        ("Cache-Control", "max-age=31536000, immutable"),
+                    ],
+                    // Both feed formats share a route kind, so we sniff the root element.
+                    RouteKind::Feed if content.get(..256).unwrap_or(&content).contains("<feed") => [
+                        ("Content-Type", "application/atom+xml; charset=utf-8"),
+                        ("Cache-Control", "max-age=500, must-revalidate"),
+                    ],
+                    RouteKind::Feed => [
+                        ("Content-Type", "application/rss+xml; charset=utf-8"),
+                        ("Cache-Control", "max-age=500, must-revalidate"),
+                    ],
+                    RouteKind::Sitemap => [
+                        ("Content-Type", "application/xml; charset=utf-8"),
+                        ("Cache-Control", "max-age=500, must-revalidate"),
+                    ],
+                    RouteKind::Robots => [
+                        ("Content-Type", "text/plain; charset=utf-8"),
+                        ("Cache-Control", "max-age=500, must-revalidate"),
+                    ],
                     _ => unreachable!()
                 };
 
@@ -321,10 +626,27 @@ impl IntoResponse for Resource {
                     content,
                 ).into_response()
             }
-            Octets(bytes) => (
+            Stream { path, len, mime, .. } => match stream_file(&path, 0..len) {
+                Ok(body) => (
+                    StatusCode::OK,
+                    [
+                        ("Content-Type", mime),
+                        ("Content-Length", len.to_string()),
+                        ("Accept-Ranges", "bytes".to_owned()),
+                        ("Cache-Control", "max-age=31536000, immutable".to_owned()),
+                    ],
+                    body,
+                ).into_response(),
+                Err(err) => {
+                    error!("Failed to stream {path:?} - {err:?}");
+                    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                }
+            },
+            Octets { bytes, mime, .. } => (
                 StatusCode::OK,
                 [
-                    ("Content-Type", "application/octet-stream"),
+                    ("Content-Type", mime.as_str()),
+                    ("Accept-Ranges", "bytes"),
                     ("Cache-Control", "max-age=31536000, immutable"),
                 ],
                 bytes,
@@ -348,6 +670,13 @@ impl IntoResponse for Resource {
                     output,
                 ).into_response()
             }
+            Redirect { code, location } => (
+                code,
+                [
+                    ("Location", location),
+                    ("Cache-Control", "max-age=500, must-revalidate".to_owned()),
+                ],
+            ).into_response(),
             Error(response, code) => (
                 code,
                 [
@@ -358,4 +687,28 @@ impl IntoResponse for Resource {
             ).into_response()
         }
     }
-}
\ No newline at end of file
+}
+
+/// Carries the query string of a request over to a redirect target, unless the target has its own.
+fn with_query(location: String, uri: &Uri) -> String {
+    match uri.query() {
+        Some(query) if !location.contains('?') => format!("{location}?{query}"),
+        _ => location,
+    }
+}
+
+/// Opens a file for streaming, yielding only the bytes within the specified range.
+fn stream_file(path: &Path, range: Range<u64>) -> Result<BoxBody> {
+    use std::io::{Seek, SeekFrom};
+
+    use tokio::io::AsyncReadExt;
+    use tokio_util::io::ReaderStream;
+
+    let mut file = std::fs::File::open(path)?;
+    file.seek(SeekFrom::Start(range.start))?;
+
+    let file = tokio::fs::File::from_std(file).take(range.end - range.start);
+    let body = axum::body::StreamBody::new(ReaderStream::new(file));
+
+    Ok(axum::body::boxed(body))
+}
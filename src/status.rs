@@ -0,0 +1,274 @@
+//! Site health reporting, as exposed by `ftl status`.
+
+use std::collections::HashSet;
+
+use console::style;
+use indicatif::HumanBytes;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::db::*;
+use crate::prelude::*;
+use crate::{prepare, revision};
+
+#[derive(Debug, Serialize)]
+struct Status {
+    root_url: String,
+    /// The most recently stabilized revision, if any.
+    stable: Option<StableRevision>,
+    /// The ID of the live revision, if any.
+    live: Option<String>,
+    /// The ID of the revision the current site source would produce.
+    working_tree: String,
+    /// Whether or not the site source has changed since the stable revision was built.
+    modified: bool,
+    counts: Counts,
+    database_size: u64,
+    cache_size: u64,
+    /// Revisions that never stabilized (typically because their build failed.)
+    unstable_revisions: Vec<String>,
+    /// Files in the asset cache that no known input file points to.
+    orphaned_cache_files: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct StableRevision {
+    id: String,
+    name: Option<String>,
+    time: Option<String>,
+    pinned: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Counts {
+    pages: i64,
+    drafts: i64,
+    hooks: i64,
+    routes: i64,
+    assets: i64,
+}
+
+record! {
+    Name   => CountsRow,
+    pages  => i64,
+    drafts => i64,
+    hooks  => i64,
+    routes => i64,
+    assets => i64
+}
+
+/// Generates and prints a status report for the site, either in a human-readable
+/// format or as JSON.
+pub fn report(ctx: &Context, json: bool) -> Result<()> {
+    let status = Status::generate(ctx)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&status)?);
+    } else {
+        status.print();
+    }
+
+    Ok(())
+}
+
+impl Status {
+    fn generate(ctx: &Context) -> Result<Self> {
+        let stable = revision::latest_stable(ctx)?;
+        let live = revision::live(ctx)?.map(|revision| revision.id);
+        let working_tree = prepare::peek_src(ctx)?.to_string();
+
+        let modified = match &stable {
+            Some(revision) => revision.id != working_tree,
+            None => true,
+        };
+
+        let counts = match &stable {
+            Some(revision) => count(ctx, &revision.id)?,
+            None => Counts::default(),
+        };
+
+        let (database_size, cache_size) = ctx.db.sizes()?;
+
+        Ok(Self {
+            root_url: ctx.root_url.clone(),
+            stable: stable.map(|revision| StableRevision {
+                id: revision.id,
+                name: revision.name,
+                time: revision.time,
+                pinned: revision.pinned,
+            }),
+            live,
+            working_tree,
+            modified,
+            counts,
+            database_size,
+            cache_size,
+            unstable_revisions: unstable_revisions(ctx)?,
+            orphaned_cache_files: orphaned_cache_files(ctx)?,
+        })
+    }
+
+    fn print(&self) {
+        let header = |title: &str| println!("{}", style(title).bold());
+
+        header("Site");
+        println!("  Root URL:        {}", self.root_url);
+
+        match &self.stable {
+            Some(stable) => {
+                let name = stable
+                    .name
+                    .as_ref()
+                    .map(|name| format!(" \"{name}\""))
+                    .unwrap_or_default();
+
+                println!(
+                    "  Stable revision: {}{name} (built {}{})",
+                    stable.id,
+                    stable.time.as_deref().unwrap_or("?"),
+                    if stable.pinned { ", pinned" } else { "" }
+                );
+            }
+            None => println!("  Stable revision: {}", style("none").yellow()),
+        }
+
+        match (&self.live, &self.stable) {
+            (Some(live), Some(stable)) if *live != stable.id => {
+                println!("  Live revision:   {live} {}", style("[ROLLED BACK]").yellow().bold());
+            }
+            (Some(live), _) => println!("  Live revision:   {live}"),
+            (None, _) => (),
+        }
+
+        let tree_status = match self.modified {
+            true => style("[MODIFIED]").yellow().bold(),
+            false => style("[UP TO DATE]").green().bold(),
+        };
+
+        println!("  Working tree:    {} {tree_status}", self.working_tree);
+        println!();
+
+        header("Contents");
+        println!("  Pages:  {} ({} drafts)", self.counts.pages, self.counts.drafts);
+        println!("  Hooks:  {}", self.counts.hooks);
+        println!("  Routes: {}", self.counts.routes);
+        println!("  Assets: {}", self.counts.assets);
+        println!();
+
+        header("Disk usage");
+        println!("  Database:    {}", HumanBytes(self.database_size));
+        println!("  Asset cache: {}", HumanBytes(self.cache_size));
+        println!("  Total:       {}", HumanBytes(self.database_size + self.cache_size));
+        println!();
+
+        header("Health");
+
+        if self.unstable_revisions.is_empty() && self.orphaned_cache_files == 0 {
+            println!("  {}", style("No problems found.").green());
+            return;
+        }
+
+        if !self.unstable_revisions.is_empty() {
+            println!(
+                "  {} unstable revision(s) left behind by failed builds:",
+                style(self.unstable_revisions.len()).yellow().bold()
+            );
+
+            for id in &self.unstable_revisions {
+                println!("    {id}");
+            }
+        }
+
+        if self.orphaned_cache_files != 0 {
+            println!(
+                "  {} orphaned file(s) in the asset cache.",
+                style(self.orphaned_cache_files).yellow().bold()
+            );
+        }
+
+        println!("  Run `ftl db compress` to clean these up.");
+    }
+}
+
+fn count(ctx: &Context, rev_id: &str) -> Result<Counts> {
+    let conn = ctx.db.get_ro()?;
+
+    let row = conn.query_row("
+        SELECT
+            (
+                SELECT COUNT(*) FROM pages
+                JOIN revision_files ON revision_files.id = pages.id
+                WHERE revision_files.revision = ?1
+                AND pages.draft = FALSE
+            ) AS pages,
+            (
+                SELECT COUNT(*) FROM pages
+                JOIN revision_files ON revision_files.id = pages.id
+                WHERE revision_files.revision = ?1
+                AND pages.draft = TRUE
+            ) AS drafts,
+            (
+                SELECT COUNT(*) FROM hooks
+                JOIN revision_files ON revision_files.id = hooks.id
+                WHERE revision_files.revision = ?1
+            ) AS hooks,
+            (
+                SELECT COUNT(*) FROM routes
+                WHERE revision = ?1
+            ) AS routes,
+            (
+                SELECT COUNT(*) FROM input_files
+                JOIN revision_files ON revision_files.id = input_files.id
+                WHERE revision_files.revision = ?1
+                AND input_files.inline = FALSE
+            ) AS assets
+    ", [rev_id], CountsRow::from_row)?;
+
+    Ok(Counts {
+        pages: row.pages,
+        drafts: row.drafts,
+        hooks: row.hooks,
+        routes: row.routes,
+        assets: row.assets,
+    })
+}
+
+fn unstable_revisions(ctx: &Context) -> Result<Vec<String>> {
+    let conn = ctx.db.get_ro()?;
+
+    let ids = conn
+        .prepare("
+            SELECT id FROM revisions
+            WHERE stable = FALSE
+            ORDER BY id
+        ")?
+        .query_and_then([], |row| row.get::<_, String>(0))?
+        .try_collect()?;
+
+    Ok(ids)
+}
+
+fn orphaned_cache_files(ctx: &Context) -> Result<u64> {
+    let conn = ctx.db.get_ro()?;
+
+    let known: HashSet<String> = conn
+        .prepare("
+            SELECT id FROM input_files
+            WHERE inline = FALSE
+        ")?
+        .query_and_then([], |row| row.get::<_, String>(0))?
+        .try_collect()?;
+
+    let mut orphaned = 0;
+
+    for entry in std::fs::read_dir(SITE_CACHE_PATH)? {
+        let entry = entry?;
+        let name = entry.file_name();
+
+        if !known.contains(&*name.to_string_lossy()) {
+            orphaned += 1;
+        }
+    }
+
+    Ok(orphaned)
+}
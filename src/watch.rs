@@ -74,6 +74,14 @@ pub fn init_watcher(ctx: &Context) -> Result<(Debouncer<impl Watcher, impl FileI
         RecursiveMode::Recursive
     )?;
 
+    // The redirects file is optional, and can't be watched if it doesn't exist.
+    if Path::new(REDIRECTS_FILENAME).is_file() {
+        debouncer.watcher().watch(
+            Path::new(REDIRECTS_FILENAME),
+            RecursiveMode::NonRecursive
+        )?;
+    }
+
     debouncer.cache().add_root(
         Path::new("."),
         RecursiveMode::Recursive